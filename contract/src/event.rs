@@ -1,4 +1,4 @@
-use near_sdk::collections::{UnorderedSet, Vector};
+use near_sdk::collections::{LookupMap, Vector};
 
 use crate::*;
 
@@ -16,23 +16,171 @@ pub struct Event {
    pub owner_account_id: AccountId,
    pub status: EventStatus,
 
-   /// list of available rewards
-   pub rewards: UnorderedSet<WrappedBalance>,
+   /// ordered list of available rewards, first one goes to the first drawn winner
+   pub rewards: Vector<Reward>,
    /// token of rewards, NEAR for None
    pub rewards_token_id: Option<TokenId>,
-   /// list of all participants
+   /// NFT prizes escrowed via nft_on_transfer, drawn after `rewards`
+   pub nft_rewards: Vector<NftReward>,
+   /// list of all participants, every account is listed once
    pub participants: Vector<AccountId>,
+   /// indexes of accounts in `participants`
+   pub participant_indices: LookupMap<AccountId, u64>,
+   /// tickets of participants, the chance to win is proportional to them
+   pub tickets: Tickets,
 
    pub allow_duplicate_participants: bool,
+   /// any account can join the event between `add_participants_start_timestamp` and `add_participants_end_timestamp`
+   pub open_registration: bool,
+   /// limit of participants with tickets
+   pub max_participants: Option<u64>,
+   /// price of a ticket in `rewards_token_id` for paid raffles
+   pub ticket_price: Option<Balance>,
+   /// shares of the prize pool in basis points, drawn after `rewards`
+   pub pool_tiers: Vec<u32>,
+   /// tokens paid for tickets
+   pub prize_pool: Balance,
+   /// number of reserve winners drawn after prizes
+   pub reserve_winners_number: u64,
+   /// ordered list of accounts which replace winners in redraw_payout
+   pub reserve_winners: Vector<AccountId>,
+   /// number of reserve winners who already got prizes
+   pub used_reserve_winners: u64,
+   /// winners pull prizes with `claim` during this period after finalization
+   pub claim_period: Option<Duration>,
+   /// backend of NEAR payouts, the contract `payout_backend` for None
+   pub payout_backend: Option<PayoutBackend>,
+   /// reward of the account which finalized the event, paid after distribution
+   pub keeper_tip: Balance,
+   /// account which completed finalization
+   pub keeper_account_id: Option<AccountId>,
+   pub keeper_tip_paid: bool,
    pub add_participants_start_timestamp: WrappedDuration,
    pub add_participants_end_timestamp: WrappedDuration,
    pub event_timestamp: WrappedDuration,
    pub finalized_timestamp: Option<WrappedDuration>,
+   /// progress of the winners draw, set by the first finalize_event call
+   pub draw: Option<DrawState>,
 
    pub title: String,
    pub description: String
 }
 
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct DrawState {
+   /// random seed of the first finalize_event call
+   pub random_seed: Vec<u8>,
+   /// DRAW_ALGORITHM_VERSION used to draw winners
+   pub algorithm_version: u8,
+   /// number of hashes used by the draw
+   pub counter: u64,
+   /// number of assigned prizes
+   pub cursor: u64,
+   /// prize pool after the service fee
+   pub prize_pool: Balance,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Reward {
+   pub amount: WrappedBalance,
+   /// prize tier, e.g. "1st place"
+   pub label: Option<String>,
+}
+
+impl Event {
+   /// Number of fungible, prize pool and NFT prizes
+   pub fn get_prizes_number(&self) -> u64 {
+      self.rewards.len() + self.pool_tiers.len() as u64 + self.nft_rewards.len()
+   }
+
+   /// Fungible rewards followed by shares of `prize_pool` and NFT prizes, in the order they are drawn
+   pub fn get_prizes(&self, prize_pool: Balance) -> Vec<(WrappedBalance, Option<String>, Option<NftReward>)> {
+      let pool_prizes = if prize_pool > 0 {
+         split_prize_pool(prize_pool, &self.pool_tiers)
+      } else {
+         vec![]
+      };
+
+      self.rewards.iter()
+         .map(|reward| (reward.amount, reward.label, None))
+         .chain(pool_prizes.into_iter().map(|amount| (U128(amount), None, None)))
+         .chain(self.nft_rewards.iter().map(|nft_reward| (U128(0), nft_reward.label.clone(), Some(nft_reward))))
+         .collect()
+   }
+
+   /// Draws a holder of a random ticket. Accounts can win only once, so tickets of winners leave the draw
+   pub fn draw_winner(&mut self, rng: &mut DrawRng) -> AccountId {
+      let ticket = rng.next_index(self.tickets.get_remaining());
+      let winner_index = self.tickets.find(ticket);
+      self.tickets.remove_from_draw(winner_index);
+      self.participants.get(winner_index).unwrap()
+   }
+
+   pub fn get_participant_index(&self, account_id: &AccountId) -> Option<u64> {
+      self.participant_indices.get(account_id)
+   }
+
+   /// Registers the participant or gives more tickets to the existing one if duplicates are allowed
+   pub fn add_participant(&mut self, account_id: &AccountId, tickets: TicketsNumber) {
+      assert!(tickets > 0, "Participant should have tickets");
+      assert!(
+         tickets == 1 || self.allow_duplicate_participants,
+         "Multiple tickets require allow_duplicate_participants"
+      );
+
+      match self.get_participant_index(account_id) {
+         Some(index) => {
+            let current_tickets = self.tickets.get(index);
+            if current_tickets == 0 {
+               // The participant left the event before
+               self.assert_participants_limit();
+               self.tickets.set(index, tickets);
+            } else if self.allow_duplicate_participants {
+               self.tickets.set(index, current_tickets + tickets);
+            }
+         }
+         None => {
+            self.assert_participants_limit();
+            self.participant_indices.insert(account_id, &self.participants.len());
+            self.participants.push(account_id);
+            self.tickets.push(tickets);
+         }
+      }
+   }
+
+   /// Participants who didn't leave the event
+   pub fn get_participants_with_tickets(&self) -> Vec<AccountId> {
+      self.participants.iter().enumerate()
+         .filter(|(index, _)| self.tickets.get(*index as u64) > 0)
+         .map(|(_, account_id)| account_id)
+         .collect()
+   }
+
+   /// Takes all tickets of the participant, the account stays in `participants` without tickets
+   pub fn remove_participant(&mut self, account_id: &AccountId) {
+      let index = self.get_participant_index(account_id).expect("ERR_NOT_PARTICIPANT");
+      assert!(self.tickets.get(index) > 0, "ERR_NOT_PARTICIPANT");
+      self.tickets.set(index, 0);
+   }
+
+   fn assert_participants_limit(&self) {
+      if let Some(max_participants) = self.max_participants {
+         assert!(self.tickets.get_holders() < max_participants, "Too many participants");
+      }
+   }
+
+   pub fn add_participants(&mut self, participants: Vec<AccountId>, tickets: Option<Vec<WrappedTicketsNumber>>) {
+      if let Some(tickets) = &tickets {
+         assert_eq!(tickets.len(), participants.len(), "Tickets should match participants");
+      }
+      let mut tickets = tickets.unwrap_or_default().into_iter();
+      for participant in participants {
+         self.add_participant(&participant, tickets.next().map(|tickets| tickets.0).unwrap_or(1));
+      }
+   }
+}
+
 impl From<VEvent> for Event {
    fn from(v_event: VEvent) -> Self {
       match v_event {
@@ -48,37 +196,101 @@ pub struct EventOutput {
    pub owner_account_id: AccountId,
    pub status: EventStatus,
    pub rewards: Vec<WrappedBalance>,
+   pub reward_labels: Vec<Option<String>>,
    pub rewards_token_id: Option<TokenId>,
+   pub nft_rewards: Vec<NftReward>,
 
    pub participants: Vec<AccountId>,
+   /// number of participants in get_participants, including ones who left the event
+   pub participants_number: U64,
+   pub total_tickets: WrappedTicketsNumber,
    pub allow_duplicate_participants: bool,
+   pub open_registration: bool,
+   pub max_participants: Option<U64>,
+   pub ticket_price: Option<WrappedBalance>,
+   pub pool_tiers: Vec<u32>,
+   pub prize_pool: WrappedBalance,
+   pub reserve_winners_number: U64,
+   pub reserve_winners: Vec<AccountId>,
+   pub used_reserve_winners: U64,
+   pub claim_period: Option<WrappedDuration>,
+   pub payout_backend: Option<PayoutBackend>,
+   pub keeper_tip: WrappedBalance,
+   pub keeper_account_id: Option<AccountId>,
+   pub keeper_tip_paid: bool,
    pub add_participants_start_timestamp: WrappedDuration,
    pub add_participants_end_timestamp: WrappedDuration,
    pub event_timestamp: WrappedDuration,
    pub finalized_timestamp: Option<WrappedDuration>,
+   pub draw: Option<DrawOutput>,
 
    pub title: String,
    pub description: String
 }
 
+/// Draw record to reproduce winners off-chain
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DrawOutput {
+   pub random_seed: Base64VecU8,
+   pub algorithm_version: u8,
+   pub counter: U64,
+   /// number of assigned prizes
+   pub cursor: U64,
+   pub prize_pool: WrappedBalance,
+}
+
+impl From<DrawState> for DrawOutput {
+   fn from(draw: DrawState) -> Self {
+      DrawOutput {
+         random_seed: draw.random_seed.into(),
+         algorithm_version: draw.algorithm_version,
+         counter: draw.counter.into(),
+         cursor: draw.cursor.into(),
+         prize_pool: draw.prize_pool.into(),
+      }
+   }
+}
+
 impl EventOutput {
    pub fn from_event(v_event: VEvent, event_id: &u64) -> Self {
       match v_event {
-         VEvent::Current(event) => EventOutput {
-            event_id: *event_id,
-            owner_account_id: event.owner_account_id,
-            status: event.status,
-            rewards: event.rewards.to_vec(),
-            rewards_token_id: event.rewards_token_id,
-            participants: event.participants.to_vec(),
-            allow_duplicate_participants: event.allow_duplicate_participants,
-            add_participants_start_timestamp: event.add_participants_start_timestamp,
-            add_participants_end_timestamp: event.add_participants_end_timestamp,
-            event_timestamp: event.event_timestamp,
-            finalized_timestamp: event.finalized_timestamp,
-            title: event.title,
-            description: event.description,
-         },
+         VEvent::Current(event) => {
+            let participants = event.get_participants_with_tickets();
+            EventOutput {
+               event_id: *event_id,
+               owner_account_id: event.owner_account_id,
+               status: event.status,
+               rewards: event.rewards.iter().map(|reward| reward.amount).collect(),
+               reward_labels: event.rewards.iter().map(|reward| reward.label).collect(),
+               rewards_token_id: event.rewards_token_id,
+               nft_rewards: event.nft_rewards.to_vec(),
+               participants,
+               participants_number: event.participants.len().into(),
+               total_tickets: event.tickets.get_total().into(),
+               allow_duplicate_participants: event.allow_duplicate_participants,
+               open_registration: event.open_registration,
+               max_participants: event.max_participants.map(|max_participants| max_participants.into()),
+               ticket_price: event.ticket_price.map(|ticket_price| ticket_price.into()),
+               pool_tiers: event.pool_tiers,
+               prize_pool: event.prize_pool.into(),
+               reserve_winners_number: event.reserve_winners_number.into(),
+               reserve_winners: event.reserve_winners.to_vec(),
+               used_reserve_winners: event.used_reserve_winners.into(),
+               claim_period: event.claim_period.map(|claim_period| claim_period.into()),
+               payout_backend: event.payout_backend,
+               keeper_tip: event.keeper_tip.into(),
+               keeper_account_id: event.keeper_account_id,
+               keeper_tip_paid: event.keeper_tip_paid,
+               add_participants_start_timestamp: event.add_participants_start_timestamp,
+               add_participants_end_timestamp: event.add_participants_end_timestamp,
+               event_timestamp: event.event_timestamp,
+               finalized_timestamp: event.finalized_timestamp,
+               draw: event.draw.map(|draw| draw.into()),
+               title: event.title,
+               description: event.description,
+            }
+         }
       }
    }
 }
@@ -87,10 +299,33 @@ impl EventOutput {
 #[serde(crate = "near_sdk::serde")]
 pub struct EventInput {
    pub rewards: Vec<WrappedBalance>,
+   /// optional prize tiers, one per reward
+   pub reward_labels: Option<Vec<String>>,
    pub rewards_token_id: Option<TokenId>,
 
    pub participants: Vec<AccountId>,
+   /// optional tickets, one per participant. Every participant has 1 ticket by default
+   pub participant_tickets: Option<Vec<WrappedTicketsNumber>>,
+   /// allows multiple tickets per participant, repeated participants get extra tickets
    pub allow_duplicate_participants: bool,
+   /// lets any account join the event with `join_event`
+   #[serde(default)]
+   pub open_registration: bool,
+   /// optional limit of participants
+   pub max_participants: Option<U64>,
+   /// makes a paid raffle, tickets are bought with `buy_tickets` or `ft_transfer_call` in `rewards_token_id`
+   pub ticket_price: Option<WrappedBalance>,
+   /// shares of the ticket sales in basis points, one per winner. Required for paid raffles
+   pub pool_tiers: Option<Vec<u32>>,
+   /// optional number of reserve winners who replace winners whose prizes are redrawn
+   pub reserve_winners_number: Option<U64>,
+   /// optional claim mode: winners `claim` prizes during this period after finalization,
+   /// unclaimed prizes go to reserve winners or back to the owner
+   pub claim_period: Option<WrappedDuration>,
+   /// optional backend of NEAR payouts, the contract one by default
+   pub payout_backend: Option<PayoutBackend>,
+   /// optional reward in `rewards_token_id` for the account which finalizes the event, paid after distribution
+   pub keeper_tip: Option<WrappedBalance>,
 
    pub add_participants_start_timestamp: WrappedDuration,
    pub add_participants_end_timestamp: WrappedDuration,
@@ -104,8 +339,39 @@ pub struct EventInput {
 #[serde(crate = "near_sdk::serde")]
 pub enum EventStatus {
    Pending,
+   /// Winners are being drawn by several finalize_event calls
+   Finalizing,
    Calculated,
-   Distributed
+   Distributed,
+   /// Cancelled by the owner before finalization, prizes are returned to the owner
+   Cancelled
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ParticipantOutput {
+   pub account_id: AccountId,
+   pub tickets: WrappedTicketsNumber,
+   /// chance to win the first drawn prize, in basis points. Later prizes are drawn without tickets of previous winners
+   pub first_prize_probability_bps: u32,
+}
+
+impl ParticipantOutput {
+   fn from_event(event: &Event, index: u64) -> Option<Self> {
+      event.participants.get(index).map(|account_id| {
+         let tickets = event.tickets.get(index);
+         let total_tickets = event.tickets.get_total();
+         ParticipantOutput {
+            account_id,
+            tickets: tickets.into(),
+            first_prize_probability_bps: if total_tickets > 0 {
+               (tickets as u128 * BASIS_POINTS as u128 / total_tickets as u128) as u32
+            } else {
+               0
+            },
+         }
+      })
+   }
 }
 
 impl Giveaway {
@@ -124,7 +390,8 @@ impl Giveaway {
       let current_timestamp = env::block_timestamp();
       (from_index..std::cmp::min(from_index + limit, self.events.len())).filter(|index| {
          let event = self.events.get(&index.clone()).unwrap();
-         event.status == EventStatus::Pending && current_timestamp >= event.event_timestamp.into()
+         event.status == EventStatus::Finalizing
+            || (event.status == EventStatus::Pending && current_timestamp >= event.event_timestamp.into())
       })
          .map(|index| self.internal_get_event_output(&index))
          .collect()
@@ -139,4 +406,17 @@ impl Giveaway {
    pub fn get_event(&self, event_id: u64) -> Option<EventOutput> {
       self.internal_get_event_output(&event_id)
    }
+
+   pub fn get_participants(&self, event_id: u64, from_index: u64, limit: u64) -> Vec<ParticipantOutput> {
+      let event = self.internal_get_event(&event_id);
+      (from_index..std::cmp::min(from_index + limit, event.participants.len()))
+         .filter_map(|index| ParticipantOutput::from_event(&event, index))
+         .collect()
+   }
+
+   pub fn get_participant(&self, event_id: u64, account_id: AccountId) -> Option<ParticipantOutput> {
+      let event = self.internal_get_event(&event_id);
+      event.get_participant_index(&account_id)
+         .and_then(|index| ParticipantOutput::from_event(&event, index))
+   }
 }
@@ -0,0 +1,111 @@
+use crate::*;
+
+/// `msg` of ft_transfer_call
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(untagged)]
+pub enum FtTransferMessage {
+   /// Add fungible token rewards to the existing pending event
+   AddRewards { event_id: EventId, rewards: Vec<WrappedBalance>, reward_labels: Option<Vec<String>> },
+   /// Buy tickets of the paid raffle
+   BuyTickets { event_id: EventId },
+   /// Create a new event with fungible token rewards
+   AddEvent(Box<EventInput>),
+}
+
+#[ext_contract(ext_ft)]
+pub trait ExtFungibleToken {
+   fn ft_transfer(&mut self, receiver_id: AccountId, amount: WrappedBalance, memo: Option<String>);
+}
+
+/// Sends a fungible token payout of the batch and marks it `Failed` in the callback if the transfer failed
+pub(crate) fn ft_transfer_payout(event_id: EventId, batch_id: PayoutBatchId, payout_index: PayoutIndex, token_id: &TokenId, payout: &Payout) -> Promise {
+   ext_ft::ft_transfer(
+      payout.account_id.to_owned(),
+      payout.amount,
+      Some(format!("Giveaway #{} reward", event_id)),
+      token_id.to_owned(),
+      ONE_YOCTO,
+      GAS_FOR_FT_TRANSFER)
+      .then(ext_self::after_payout_transfer(
+         event_id,
+         batch_id,
+         payout_index,
+         env::current_account_id(),
+         NO_DEPOSIT,
+         GAS_FOR_AFTER_PAYOUT_TRANSFER,
+      ))
+}
+
+#[near_bindgen]
+impl Giveaway {
+   /// `msg` is either a JSON-serialized `EventInput` to create an event with fungible token rewards,
+   /// `{"event_id": .., "rewards": [..], "reward_labels": [..]}` to add rewards to a pending event of the sender
+   /// or `{"event_id": ..}` to buy tickets of the paid raffle, the storage of a new buyer is paid from `deposit_storage`.
+   /// Event `rewards_token_id` should match the whitelisted token being transferred.
+   /// Unused tokens are returned to the sender
+   pub fn ft_on_transfer(&mut self, sender_id: AccountId, amount: WrappedBalance, msg: String) -> PromiseOrValue<WrappedBalance> {
+      let token_id = Some(env::predecessor_account_id());
+      let message: FtTransferMessage = near_sdk::serde_json::from_str(&msg).expect("ERR_WRONG_MSG_FORMAT");
+
+      let tokens_to_return = match message {
+         FtTransferMessage::AddRewards { event_id, rewards, reward_labels } => {
+            self.assert_active();
+            let mut event: Event = self.internal_get_event(&event_id);
+            assert_eq!(event.status, EventStatus::Pending, "Already finalized");
+            assert_eq!(sender_id, event.owner_account_id, "ERR_NO_ACCESS");
+            assert_eq!(event.rewards_token_id, token_id, "ERR_WRONG_TOKEN");
+            assert!(!rewards.is_empty(), "Missing rewards");
+            assert!(event.get_prizes_number() + (rewards.len() as u64) < MAX_GIVEAWAY_WINNERS as u64, "Too many rewards");
+            if let Some(reward_labels) = &reward_labels {
+               assert_eq!(reward_labels.len(), rewards.len(), "Reward labels should match rewards");
+               reward_labels.iter().for_each(|label| assert_valid_reward_label(label));
+            }
+
+            let mut total: Balance = 0;
+            for amount in &rewards {
+               assert!(amount.0 > 0, "Rewards should be positive");
+               total += amount.0;
+            }
+            let service_fee = self.get_service_fee(&token_id, &sender_id, &total);
+            let payment: Balance = total + service_fee;
+            assert!(
+               payment <= amount.0,
+               "Not enough attached tokens to provide rewards (Attached: {}. Total rewards: {}, Service commission: {})",
+               amount.0, total, service_fee
+            );
+            self.internal_add_service_fee(&token_id, &service_fee);
+
+            let mut reward_labels = reward_labels.unwrap_or_default().into_iter();
+            for input_reward in rewards {
+               event.rewards.push(&Reward {
+                  amount: input_reward,
+                  label: reward_labels.next(),
+               });
+            }
+            self.events.insert(&event_id, &event);
+            log!("@{} added {} of {} to event {}", sender_id, total, env::predecessor_account_id(), event_id);
+            amount.0 - payment
+         }
+         FtTransferMessage::BuyTickets { event_id } => {
+            let (tokens_to_return, storage_cost) = self.internal_buy_tickets(event_id, &sender_id, &token_id, amount.0);
+            self.internal_charge_storage_deposit(&sender_id, storage_cost);
+            tokens_to_return
+         }
+         FtTransferMessage::AddEvent(event_input) => {
+            assert_eq!(event_input.rewards_token_id, token_id, "ERR_WRONG_TOKEN");
+            self.assert_min_deposit(&event_input.rewards_token_id, amount.0);
+
+            let (event_id, tokens_to_return) = self.internal_add_event(sender_id.clone(), *event_input, amount.0, None);
+            log!("@{} added event {}", sender_id, event_id);
+            tokens_to_return
+         }
+      };
+
+      if tokens_to_return > 0 {
+         log!("@{} withdrawing extra {}", sender_id, tokens_to_return);
+      }
+
+      PromiseOrValue::Value(U128(tokens_to_return))
+   }
+}
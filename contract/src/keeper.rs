@@ -0,0 +1,116 @@
+use crate::*;
+use crate::utils::is_promise_success;
+
+impl Giveaway {
+   /// Sends the keeper tip of a distributed event to the account which finalized it.
+   /// Tips of cancelled events return to the event owner
+   pub(crate) fn internal_pay_keeper_tip(&mut self, event_id: EventId, event: &mut Event) -> Option<Promise> {
+      if event.keeper_tip == 0 || event.keeper_tip_paid {
+         return None;
+      }
+      let keeper_account_id = event.keeper_account_id.clone()?;
+      event.keeper_tip_paid = true;
+      log!("@{} receives keeper tip of {}", keeper_account_id, event.keeper_tip);
+
+      let transfer = match &event.rewards_token_id {
+         Some(token_id) => ext_ft::ft_transfer(
+            keeper_account_id,
+            event.keeper_tip.into(),
+            Some(format!("Giveaway #{} keeper tip", event_id)),
+            token_id.to_owned(),
+            ONE_YOCTO,
+            GAS_FOR_FT_TRANSFER),
+         None => Promise::new(keeper_account_id).transfer(event.keeper_tip),
+      };
+      Some(transfer.then(ext_self::after_keeper_tip_transfer(
+         event_id,
+         env::current_account_id(),
+         NO_DEPOSIT,
+         GAS_FOR_AFTER_PAYOUT_TRANSFER,
+      )))
+   }
+}
+
+#[near_bindgen]
+impl Giveaway {
+   #[private]
+   pub fn after_keeper_tip_transfer(&mut self, event_id: EventId) -> bool {
+      let promise_success = is_promise_success();
+      if !promise_success {
+         let mut event = self.internal_get_event(&event_id);
+         log!("Keeper tip of event {} failed", event_id);
+         event.keeper_tip_paid = false;
+         self.events.insert(&event_id, &event);
+      }
+
+      promise_success
+   }
+
+   /// Sends the keeper tip again if the transfer failed
+   pub fn pay_keeper_tip(&mut self, event_id: EventId) -> Promise {
+      self.assert_active();
+      let mut event = self.internal_get_event(&event_id);
+      assert!(
+         matches!(event.status, EventStatus::Distributed | EventStatus::Cancelled),
+         "Keeper tip is paid after distribution"
+      );
+
+      let promise = self.internal_pay_keeper_tip(event_id, &mut event).expect("No keeper tip to pay");
+      self.events.insert(&event_id, &event);
+      promise
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use near_sdk::test_utils::accounts;
+   use near_sdk::PromiseResult;
+
+   use crate::tests::*;
+
+   use super::*;
+
+   /// Distributed event finalized by accounts(5), its keeper tip is sent
+   fn setup_distributed_event() -> (Giveaway, EventId) {
+      let mut event_input = get_event_input(1, &[1, 1], 0);
+      event_input.keeper_tip = Some(U128(ONE_NEAR));
+      let (mut contract, event_id) = add_event(event_input);
+
+      set_context(accounts(5), 0);
+      assert!(contract.finalize_event(event_id, None));
+      contract.distribute_payouts(event_id, None, None);
+      // The multisender batch takes all prepaid gas of the call
+      set_context(accounts(5), 0);
+      contract.close_event(event_id);
+
+      let event = contract.internal_get_event(&event_id);
+      assert_eq!(event.status, EventStatus::Distributed);
+      assert_eq!(event.keeper_account_id, Some(accounts(5)));
+      assert!(event.keeper_tip_paid);
+      (contract, event_id)
+   }
+
+   #[test]
+   #[should_panic(expected = "No keeper tip to pay")]
+   fn test_keeper_tip_paid_once() {
+      let (mut contract, event_id) = setup_distributed_event();
+      set_promise_result(PromiseResult::Successful(vec![]));
+      assert!(contract.after_keeper_tip_transfer(event_id));
+      assert!(contract.internal_get_event(&event_id).keeper_tip_paid);
+
+      set_context(accounts(5), 0);
+      contract.pay_keeper_tip(event_id);
+   }
+
+   #[test]
+   fn test_pay_keeper_tip_after_failed_transfer() {
+      let (mut contract, event_id) = setup_distributed_event();
+      set_promise_result(PromiseResult::Failed);
+      assert!(!contract.after_keeper_tip_transfer(event_id));
+      assert!(!contract.internal_get_event(&event_id).keeper_tip_paid);
+
+      set_context(accounts(5), 0);
+      contract.pay_keeper_tip(event_id);
+      assert!(contract.internal_get_event(&event_id).keeper_tip_paid);
+   }
+}
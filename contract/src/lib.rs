@@ -1,17 +1,37 @@
-use near_sdk::{AccountId, Balance, BorshStorageKey, env, ext_contract, Gas, log, near_bindgen, PanicOnDefault, Promise};
+use near_sdk::{AccountId, Balance, BorshStorageKey, env, ext_contract, Gas, log, near_bindgen, PanicOnDefault, Promise, PromiseOrValue};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LookupSet, UnorderedMap, UnorderedSet, Vector};
-use near_sdk::json_types::{U128, U64};
+use near_sdk::collections::{LookupMap, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::json_types::{Base64VecU8, U128, U64};
 use near_sdk::serde::{Deserialize, Serialize};
 
 use crate::event::*;
+use crate::fungible_token::*;
+use crate::migration::LegacyState;
 use crate::multisender::*;
+use crate::non_fungible_token::*;
 use crate::payout::*;
-use crate::utils::get_service_fee;
+use crate::raffle::*;
+use crate::random::DrawRng;
+use crate::roles::*;
+use crate::service_fee::*;
+use crate::storage::get_storage_cost;
+use crate::tickets::*;
+use crate::whitelist::*;
+use crate::utils::{assert_participants_update_allowed, assert_valid_reward_label};
 
 mod event;
+mod fungible_token;
+mod keeper;
+mod migration;
 mod payout;
+mod raffle;
+mod random;
+mod roles;
+mod tickets;
 mod multisender;
+mod non_fungible_token;
+mod service_fee;
+mod storage;
 mod whitelist;
 mod utils;
 
@@ -23,36 +43,112 @@ const MAX_GIVEAWAY_WINNERS: usize = 128;
 const MIN_DEPOSIT_AMOUNT: Balance = 10_000_000_000_000_000_000_000;
 const MAX_DESCRIPTION_LENGTH: usize = 280;
 const MAX_TITLE_LENGTH: usize = 128;
+const MAX_REWARD_LABEL_LENGTH: usize = 64;
+const MAX_REDRAW_REASON_LENGTH: usize = 128;
+const FINALIZE_BATCH_SIZE: u64 = 32;
+const MAX_RESERVE_WINNERS: u64 = 16;
+/// failed payouts are retried up to this number of attempts, then they can only be redrawn
+const MAX_PAYOUT_ATTEMPTS: u32 = 3;
+/// time the receiver of a failed payout has to fix the failure before the event owner can retry it, 1 day
+const PAYOUT_RETRY_DELAY: Duration = 24 * 60 * 60 * 1_000_000_000;
+/// version of the winners draw described in random.rs, stored with every draw
+const DRAW_ALGORITHM_VERSION: u8 = 1;
+/// verify_draw reads tickets of all participants in one view call, larger events are verified off-chain with giveaway-verify
+const MAX_VERIFY_DRAW_PARTICIPANTS: u64 = 1_000;
 
 const NO_DEPOSIT: Balance = 0;
 const BASE_PAYOUT_PREPARATION_GAS: Gas = Gas(25_000_000_000_000);
 const GAS_FOR_AFTER_MULTISEND: Gas = Gas(25_000_000_000_000);
-const SERVICE_FEE_NUMERATOR: u64 = 100;
-const SERVICE_FEE_DENOMINATOR: u64 = 10000;
-const MAX_SERVICE_FEE: Balance = 10_000_000_000_000_000_000_000_000;
+/// estimated gas of the multisender call without transfers
+const GAS_FOR_MULTISEND: Gas = Gas(10_000_000_000_000);
+/// estimated gas of every multisender transfer
+const GAS_FOR_MULTISEND_PER_ACCOUNT: Gas = Gas(3_000_000_000_000);
+/// gas to prepare a payout in distribute_payouts
+const GAS_PER_PAYOUT: Gas = Gas(1_000_000_000_000);
+const GAS_FOR_FT_TRANSFER: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_NFT_TRANSFER: Gas = Gas(15_000_000_000_000);
+const GAS_FOR_AFTER_PAYOUT_TRANSFER: Gas = Gas(5_000_000_000_000);
+const GAS_FOR_AFTER_WITHDRAW_SERVICE_FEE: Gas = Gas(5_000_000_000_000);
+const GAS_FOR_AFTER_FINALIZE_AND_DISTRIBUTE: Gas = Gas(10_000_000_000_000);
+const GAS_FOR_KEEPER_TIP: Gas = Gas(GAS_FOR_FT_TRANSFER.0 + GAS_FOR_AFTER_PAYOUT_TRANSFER.0);
+const ONE_YOCTO: Balance = 1;
+const BASIS_POINTS: u32 = 10_000;
+const DEFAULT_SERVICE_FEE_BPS: u32 = 100;
+const DEFAULT_NEAR_MAX_SERVICE_FEE: Balance = 10_000_000_000_000_000_000_000_000;
 
 #[ext_contract(ext_self)]
 pub trait ExtContract {
     fn after_multisend_attached_tokens(
         &mut self,
         event_id: u64,
-        accounts: Vec<MultisenderPayout>,
+        batch_id: PayoutBatchId,
+        payout_indices: Vec<PayoutIndex>,
+    ) -> bool;
+
+    fn after_payout_transfer(
+        &mut self,
+        event_id: u64,
+        batch_id: PayoutBatchId,
+        payout_index: PayoutIndex,
+    ) -> bool;
+
+    fn after_withdraw_service_fee(
+        &mut self,
+        withdrawal_index: u64,
+    ) -> bool;
+
+    fn after_finalize_and_distribute(
+        &mut self,
+        event_id: u64,
+    ) -> bool;
+
+    fn after_keeper_tip_transfer(
+        &mut self,
+        event_id: u64,
+    ) -> bool;
+
+    fn after_refund_tickets(
+        &mut self,
+        event_id: u64,
+        account_id: AccountId,
+        tickets: WrappedTicketsNumber,
     ) -> bool;
 }
 
+/// State of the contract. Deployments of the first release are upgraded with `migrate` and `migrate_events`
 #[near_bindgen]
 #[derive(BorshDeserialize, BorshSerialize, PanicOnDefault)]
 pub struct Giveaway {
     owner_id: AccountId,
+    /// New owner, who should accept the ownership
+    proposed_owner_id: Option<AccountId>,
+    pause_guardians: UnorderedSet<AccountId>,
+    fee_managers: UnorderedSet<AccountId>,
     active: bool,
     next_event_id: u64,
     events: UnorderedMap<EventId, Event>,
     payouts: UnorderedMap<EventId, Vec<Payout>>,
-    /// Whitelisted tokens. None for native NEAR
-    whitelisted_tokens: LookupSet<TokenId>,
+    /// Whitelisted fungible tokens with their metadata
+    whitelisted_tokens: UnorderedMap<TokenId, WhitelistedToken>,
+    /// NFT contracts which can escrow prizes and create events with nft_transfer_call
+    whitelisted_nft_contracts: UnorderedSet<AccountId>,
     /// Contract of multisender app
     multisender_contract: AccountId,
-    total_service_fee: UnorderedMap<Option<TokenId>, Balance>
+    /// Backend of NEAR payouts for events without their own
+    payout_backend: PayoutBackend,
+    next_payout_batch_id: PayoutBatchId,
+    total_service_fee: UnorderedMap<Option<TokenId>, Balance>,
+    service_fee_withdrawals: Vector<ServiceFeeWithdrawal>,
+    /// Fee for tokens without their own policy
+    default_service_fee: ServiceFeePolicy,
+    /// Fee policies per token. None for native NEAR
+    token_service_fees: UnorderedMap<Option<TokenId>, ServiceFeePolicy>,
+    /// Partner accounts which create events without service fee
+    fee_exempt_accounts: UnorderedSet<AccountId>,
+    /// NEAR prepaid for storage by accounts which buy tickets with fungible tokens
+    storage_deposits: LookupMap<AccountId, Balance>,
+    /// Events of the first release waiting for `migrate_events`
+    migration: Option<LegacyState>,
 }
 
 #[derive(BorshStorageKey, BorshSerialize)]
@@ -61,24 +157,60 @@ pub(crate) enum StorageKey {
     Payouts,
     EventRewards { event_id: u64 },
     EventParticipants { event_id: u64 },
+    EventTickets { event_id: u64 },
+    EventTicketsTree { event_id: u64 },
+    EventNftRewards { event_id: u64 },
     WhitelistedTokens,
-    TotalServiceFee
+    TotalServiceFee,
+    ServiceFeeWithdrawals,
+    TokenServiceFees,
+    FeeExemptAccounts,
+    PauseGuardians,
+    FeeManagers,
+    EventParticipantIndices { event_id: u64 },
+    EventReserveWinners { event_id: u64 },
+    WhitelistedNftContracts,
+    StorageDeposits,
+    /// events and payouts of upgraded deployments, the first release keeps its own at `Events` and `Payouts`
+    MigratedEvents,
+    MigratedPayouts,
 }
 
 #[near_bindgen]
 impl Giveaway {
     #[init]
     pub fn new(owner_id: AccountId, multisender_contract: Option<AccountId>) -> Self {
+        let mut token_service_fees = UnorderedMap::new(StorageKey::TokenServiceFees);
+        token_service_fees.insert(&None, &ServiceFeePolicy {
+            fee_bps: DEFAULT_SERVICE_FEE_BPS,
+            max_fee: Some(U128(DEFAULT_NEAR_MAX_SERVICE_FEE)),
+        });
+
         //  multisender.app.near for Mainnet, dev-1611689128537-1966413 for Testnet
         Self {
             owner_id,
+            proposed_owner_id: None,
+            pause_guardians: UnorderedSet::new(StorageKey::PauseGuardians),
+            fee_managers: UnorderedSet::new(StorageKey::FeeManagers),
             active: true,
             next_event_id: 0,
             events: UnorderedMap::new(StorageKey::Events),
             payouts: UnorderedMap::new(StorageKey::Payouts),
-            whitelisted_tokens: LookupSet::new(StorageKey::WhitelistedTokens),
+            whitelisted_tokens: UnorderedMap::new(StorageKey::WhitelistedTokens),
+            whitelisted_nft_contracts: UnorderedSet::new(StorageKey::WhitelistedNftContracts),
             multisender_contract: multisender_contract.unwrap_or_else(|| AccountId::new_unchecked("multisender.app.near".to_string())),
+            payout_backend: PayoutBackend::Multisender,
+            next_payout_batch_id: 0,
             total_service_fee: UnorderedMap::new(StorageKey::TotalServiceFee),
+            service_fee_withdrawals: Vector::new(StorageKey::ServiceFeeWithdrawals),
+            default_service_fee: ServiceFeePolicy {
+                fee_bps: DEFAULT_SERVICE_FEE_BPS,
+                max_fee: None,
+            },
+            token_service_fees,
+            fee_exempt_accounts: UnorderedSet::new(StorageKey::FeeExemptAccounts),
+            storage_deposits: LookupMap::new(StorageKey::StorageDeposits),
+            migration: None,
         }
     }
 
@@ -88,17 +220,322 @@ impl Giveaway {
 
     #[payable]
     pub fn add_event(&mut self, event_input: EventInput) -> u64 {
-        self.assert_active();
-        self.assert_whitelisted_token(&event_input.rewards_token_id);
+        assert!(event_input.rewards_token_id.is_none(), "Use ft_transfer_call to add events with fungible token rewards");
 
         let tokens: Balance = env::attached_deposit();
+        self.assert_min_deposit(&None, tokens);
+
+        let owner_id = env::predecessor_account_id();
+        let (event_id, tokens_to_return) = self.internal_add_event(owner_id.clone(), event_input, tokens, None);
+
+        if tokens_to_return > 0 {
+            log!("@{} withdrawing extra {}", owner_id, tokens_to_return);
+            Promise::new(owner_id).transfer(tokens_to_return);
+        }
+
+        event_id
+    }
+
+    /// Adds participants with optional `tickets`, one per participant. Every participant has 1 ticket by default
+    pub fn insert_participants(&mut self, event_id: u64, participants: Vec<AccountId>, tickets: Option<Vec<WrappedTicketsNumber>>) {
+        self.assert_active();
+        let mut event: Event = self.internal_get_event(&event_id);
+
+        assert_participants_update_allowed(&event);
+        self.assert_event_owner(&event);
+        assert!(event.ticket_price.is_none(), "Participants of paid raffles should buy tickets");
+
+        event.add_participants(participants, tickets);
+
+        self.events.insert(&event_id, &event);
+    }
+
+    /// Adds the caller to the event with open registration. The caller pays for the storage of the new participant,
+    /// the rest of the deposit is returned
+    #[payable]
+    pub fn join_event(&mut self, event_id: u64) {
+        self.assert_active();
+        let mut event: Event = self.internal_get_event(&event_id);
+
+        assert!(event.open_registration, "Registration is closed");
+        assert!(event.ticket_price.is_none(), "Buy tickets to join the event");
+        assert_participants_update_allowed(&event);
+
+        let account_id = env::predecessor_account_id();
+        if let Some(index) = event.get_participant_index(&account_id) {
+            assert_eq!(event.tickets.get(index), 0, "Already joined");
+        }
+        let initial_storage_usage = env::storage_usage();
+        event.add_participant(&account_id, 1);
+        self.events.insert(&event_id, &event);
+
+        let storage_cost = get_storage_cost(initial_storage_usage);
+        let deposit = env::attached_deposit();
+        assert!(deposit >= storage_cost, "Not enough deposit to pay for storage (Attached: {}, Required: {})", deposit, storage_cost);
+        if deposit > storage_cost {
+            Promise::new(account_id.clone()).transfer(deposit - storage_cost);
+        }
+        log!("@{} joined event {}", account_id, event_id);
+    }
+
+    /// Removes the caller from the event with open registration before participants deadline
+    pub fn leave_event(&mut self, event_id: u64) {
+        self.assert_active();
+        let mut event: Event = self.internal_get_event(&event_id);
+
+        assert!(event.open_registration, "Registration is closed");
+        assert!(event.ticket_price.is_none(), "Paid tickets can't be returned");
+        assert_participants_update_allowed(&event);
+
+        let account_id = env::predecessor_account_id();
+        event.remove_participant(&account_id);
+
+        self.events.insert(&event_id, &event);
+        log!("@{} left event {}", account_id, event_id);
+    }
+
+    /// Draws up to `limit` winners of prizes and then reserve winners, `FINALIZE_BATCH_SIZE` by default.
+    /// The first call fixes the random seed, large events are finalized in several calls. Returns true when all winners are drawn
+    pub fn finalize_event(&mut self, event_id: u64, limit: Option<u64>) -> bool {
+        self.assert_active();
+        let mut event: Event = self.internal_get_event(&event_id);
+
+        let mut draw = if event.status == EventStatus::Pending {
+            assert!(event.get_prizes_number() > 0, "Rewards Missing");
+            assert!(event.tickets.get_total() > 0, "Participants Missing");
+
+            assert!(
+                env::block_timestamp() >= event.event_timestamp.0,
+                "It's too early to finalize the event. Please wait for block {}",
+                event.event_timestamp.0
+            );
+
+            event.status = EventStatus::Finalizing;
+            DrawState {
+                random_seed: env::random_seed(),
+                algorithm_version: DRAW_ALGORITHM_VERSION,
+                counter: 0,
+                cursor: 0,
+                prize_pool: self.internal_take_prize_pool_fee(&event),
+            }
+        } else {
+            assert_eq!(event.status, EventStatus::Finalizing, "Already finalized");
+            event.draw.take().expect("ERR_NO_DRAW")
+        };
+
+        let mut rng = DrawRng::new(draw.random_seed.clone(), event_id, draw.counter);
+        let mut payouts = self.internal_get_payouts(&event_id);
+        let prizes = event.get_prizes(draw.prize_pool);
+        let prizes_number = prizes.len() as u64;
+        // Reserve winners are drawn after prizes to replace winners in redraw_payout
+        let draw_size = prizes_number + event.reserve_winners_number;
+        let batch_end = std::cmp::min(draw.cursor + limit.unwrap_or(FINALIZE_BATCH_SIZE), draw_size);
+        for (prize_index, (reward, label, nft_reward)) in prizes.into_iter().enumerate()
+            .take(batch_end as usize)
+            .skip(draw.cursor as usize) {
+            let refund = event.tickets.get_remaining() == 0;
+            let account_id = if refund {
+                // All participants got their prizes, the rest returns to the event owner
+                log!("Prize #{} wasn't awarded and returns to @{}", prize_index + 1, event.owner_account_id);
+                event.owner_account_id.clone()
+            } else {
+                let winner_account_id = event.draw_winner(&mut rng);
+
+                if let Some(nft_reward) = &nft_reward {
+                    log!("@{} won NFT {} of {}", winner_account_id, nft_reward.token_id, nft_reward.contract_id);
+                } else {
+                    log!("@{} won reward of {} yNEAR", winner_account_id, reward.0);
+                }
+                winner_account_id
+            };
+
+            payouts.push(Payout::new(
+                account_id,
+                reward,
+                event.rewards_token_id.to_owned(),
+                nft_reward,
+                prize_index as u64 + 1,
+                label,
+                refund,
+            ));
+            draw.cursor += 1;
+        }
+
+        while draw.cursor < batch_end {
+            if event.tickets.get_remaining() == 0 {
+                draw.cursor = draw_size;
+                break;
+            }
+            let reserve_account_id = event.draw_winner(&mut rng);
+            log!("@{} is reserve winner #{}", reserve_account_id, event.reserve_winners.len() + 1);
+            event.reserve_winners.push(&reserve_account_id);
+            draw.cursor += 1;
+        }
+
+        draw.counter = rng.get_counter();
+        let finalized = draw.cursor >= draw_size;
+        if finalized {
+            event.status = EventStatus::Calculated;
+            event.finalized_timestamp = Some(env::block_timestamp().into());
+            // The keeper tip goes to this account after distribution
+            event.keeper_account_id = Some(env::predecessor_account_id());
+            if let Some(claim_period) = event.claim_period {
+                let claim_deadline = env::block_timestamp() + claim_period;
+                for payout in payouts.iter_mut().filter(|payout| !payout.refund) {
+                    payout.claim_deadline = Some(claim_deadline.into());
+                }
+            }
+        } else {
+            log!("{} of {} winners drawn", draw.cursor, draw_size);
+        }
+        event.draw = Some(draw);
+
+        self.events.insert(&event_id, &event);
+        self.payouts.insert(&event_id, &payouts);
+
+        finalized
+    }
+
+    /// Draws all winners and sends all payouts of a small event in one transaction.
+    /// The event becomes `Distributed` in the callback if all payouts succeed, failed payouts stay `Failed`
+    pub fn finalize_and_distribute(&mut self, event_id: u64) -> PromiseOrValue<bool> {
+        let event: Event = self.internal_get_event(&event_id);
+        assert!(event.claim_period.is_none(), "Prizes of claim mode events are claimed by winners");
+
+        let finalized = self.finalize_event(event_id, Some(MAX_GIVEAWAY_WINNERS as u64 + MAX_RESERVE_WINNERS));
+        assert!(finalized, "Event is too large, use finalize_event");
+
+        let callback_gas = if event.keeper_tip > 0 {
+            GAS_FOR_AFTER_FINALIZE_AND_DISTRIBUTE + GAS_FOR_KEEPER_TIP
+        } else {
+            GAS_FOR_AFTER_FINALIZE_AND_DISTRIBUTE
+        };
+        let (promise, next_index) = self.internal_distribute_payouts(event_id, 0, None, callback_gas);
+        assert!(next_index.is_none(), "Not enough gas to send all payouts, use distribute_payouts");
+
+        match promise {
+            Some(promise) => PromiseOrValue::Promise(promise.then(ext_self::after_finalize_and_distribute(
+                event_id,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                callback_gas,
+            ))),
+            None => PromiseOrValue::Value(self.after_finalize_and_distribute(event_id)),
+        }
+    }
+
+    #[private]
+    pub fn after_finalize_and_distribute(&mut self, event_id: u64) -> bool {
+        let mut event: Event = self.internal_get_event(&event_id);
+        // Payout callbacks ran before, so only successful payouts are complete
+        let distributed = event.status == EventStatus::Calculated
+            && self.internal_get_payouts(&event_id).iter().all(|payout| payout.status == PayoutStatus::Complete);
+        if distributed {
+            log!("All payouts distributed");
+            event.status = EventStatus::Distributed;
+            self.internal_pay_keeper_tip(event_id, &mut event);
+            self.events.insert(&event_id, &event);
+        } else {
+            log!("Some payouts of event {} failed", event_id);
+        }
+
+        distributed
+    }
+
+    /// Sends pending payouts starting from `from_index`, as many as the prepaid gas allows and at most `limit`.
+    /// Returns `from_index` of the next call, None when all payouts are sent or nothing is pending.
+    /// Transfers finish after this call, their results are read from `status`, `batch_id` and `batch_outcome` of get_payouts
+    pub fn distribute_payouts(&mut self, event_id: u64, from_index: Option<u64>, limit: Option<u64>) -> Option<U64> {
+        assert_ne!(limit, Some(0), "Limit should be positive");
+        // Transfers are scheduled when the promise is dropped
+        let (_promise, next_index) = self.internal_distribute_payouts(event_id, from_index.unwrap_or_default(), limit, Gas(0));
+        next_index.map(|next_index| next_index.into())
+    }
+
+    /// Cancels a pending event and returns all prizes to the owner, buyers of tickets get their payments back with `refund_tickets`.
+    /// The service fee is not refunded. Returns `from_index` for distribute_payouts if not all prizes fit into one batch
+    pub fn cancel_event(&mut self, event_id: u64) -> Option<U64> {
+        self.assert_active();
+        let mut event: Event = self.internal_get_event(&event_id);
+        assert_eq!(event.status, EventStatus::Pending, "Already finalized");
+        self.assert_event_owner(&event);
+
+        event.status = EventStatus::Cancelled;
+        event.keeper_account_id = Some(event.owner_account_id.clone());
+        self.internal_pay_keeper_tip(event_id, &mut event);
+
+        let payouts: Vec<Payout> = event.get_prizes(0).into_iter().enumerate()
+            .map(|(prize_index, (reward, label, nft_reward))| Payout::new(
+                event.owner_account_id.clone(),
+                reward,
+                event.rewards_token_id.to_owned(),
+                nft_reward,
+                prize_index as u64 + 1,
+                label,
+                true,
+            ))
+            .collect();
+
+        self.events.insert(&event_id, &event);
+        self.payouts.insert(&event_id, &payouts);
+        log!("Event {} cancelled", event_id);
+
+        if payouts.is_empty() {
+            return None;
+        }
+        self.distribute_payouts(event_id, None, None)
+    }
+
+    pub fn close_event(&mut self, event_id: u64) {
+        self.assert_active();
+        let mut event: Event = self.internal_get_event(&event_id);
+        assert_eq!(event.status, EventStatus::Calculated, "Method is not available");
+
+        // Payouts include unawarded prizes returned to the owner, so they are refunded before closing
+        let payouts = self.internal_get_payouts(&event_id);
+        for payout in payouts {
+            assert_eq!(payout.status, PayoutStatus::Complete, "Payouts still pending");
+        }
+
+        log!("All payouts distributed");
+        event.status = EventStatus::Distributed;
+        self.internal_pay_keeper_tip(event_id, &mut event);
+        self.events.insert(&event_id, &event);
+    }
+}
+
+impl Giveaway {
+    /// Creates an event funded with `tokens` of `event_input.rewards_token_id` and an optional NFT prize.
+    /// Returns the event id and the amount of extra tokens to return to the owner
+    pub(crate) fn internal_add_event(&mut self, owner_id: AccountId, event_input: EventInput, tokens: Balance, nft_reward: Option<NftReward>) -> (EventId, Balance) {
+        self.assert_active();
+        self.assert_whitelisted_token(&event_input.rewards_token_id);
 
         let rewards_number = event_input.rewards.len();
-        assert!(rewards_number < MAX_GIVEAWAY_WINNERS, "Too many rewards");
-        assert!(rewards_number > 0, "Missing rewards");
-        assert!(tokens >= MIN_DEPOSIT_AMOUNT, "Not enough deposit");
+        let pool_tiers = event_input.pool_tiers.unwrap_or_default();
+        assert!(rewards_number + pool_tiers.len() < MAX_GIVEAWAY_WINNERS, "Too many rewards");
+        assert!(rewards_number > 0 || !pool_tiers.is_empty() || nft_reward.is_some(), "Missing rewards");
+        if let Some(ticket_price) = event_input.ticket_price {
+            // Buyers pay for their storage, so tickets are worth more than a participant record
+            assert!(ticket_price.0 >= self.get_min_deposit(&event_input.rewards_token_id), "Ticket price is too low");
+            assert_valid_pool_tiers(&pool_tiers);
+            // Free tickets would dilute the chances of buyers
+            assert!(event_input.participants.is_empty(), "Participants of paid raffles should buy tickets");
+            assert!(event_input.open_registration, "Paid raffles require open registration");
+        } else {
+            assert!(pool_tiers.is_empty(), "Pool tiers require ticket price");
+        }
+        let reserve_winners_number = event_input.reserve_winners_number.map(|number| number.0).unwrap_or(0);
+        assert!(reserve_winners_number <= MAX_RESERVE_WINNERS, "Too many reserve winners");
+        if let Some(claim_period) = event_input.claim_period {
+            assert!(claim_period.0 > 0, "Claim period should be positive");
+        }
         assert!(event_input.description.len() < MAX_DESCRIPTION_LENGTH, "Description length is too long");
         assert!(event_input.title.len() < MAX_TITLE_LENGTH, "Title length is too long");
+        if let Some(reward_labels) = &event_input.reward_labels {
+            assert_eq!(reward_labels.len(), rewards_number, "Reward labels should match rewards");
+            reward_labels.iter().for_each(|label| assert_valid_reward_label(label));
+        }
 
         let current_timestamp: Duration = env::block_timestamp();
         // TODO remove. Difficult to test
@@ -109,224 +546,359 @@ impl Giveaway {
         );
 
         let event_id = self.next_event_id;
-        let owner_id = env::predecessor_account_id();
 
         let mut total: Balance = 0;
         for amount in &event_input.rewards {
+            // Token contracts reject transfers of 0, such payouts would never be sent
+            assert!(amount.0 > 0, "Rewards should be positive");
             total += amount.0;
         }
 
-        let service_fee = get_service_fee(&total);
-        let payment: Balance = total + service_fee;
+        let service_fee = self.get_service_fee(&event_input.rewards_token_id, &owner_id, &total);
+        // The keeper tip is escrowed with rewards and has no service fee
+        let keeper_tip: Balance = event_input.keeper_tip.map(|keeper_tip| keeper_tip.0).unwrap_or(0);
+        let payment: Balance = total + service_fee + keeper_tip;
         self.internal_add_service_fee(&event_input.rewards_token_id, &service_fee);
 
         assert!(
             payment <= tokens,
-            "Not enough attached tokens to provide rewards (Attached: {}. Total rewards: {}, Service commission: {})",
-            tokens, total, service_fee
+            "Not enough attached tokens to provide rewards (Attached: {}. Total rewards: {}, Service commission: {}, Keeper tip: {})",
+            tokens, total, service_fee, keeper_tip
         );
 
-        if payment < tokens {
-            let tokens_to_return = tokens - payment;
-            log!("@{} withdrawing extra {}", owner_id, tokens_to_return);
-            Promise::new(owner_id.clone()).transfer(tokens_to_return);
-        }
-
-        let mut rewards = UnorderedSet::new(StorageKey::EventRewards { event_id });
+        let mut rewards = Vector::new(StorageKey::EventRewards { event_id });
+        let mut reward_labels = event_input.reward_labels.unwrap_or_default().into_iter();
         for input_reward in event_input.rewards {
-            rewards.insert(&input_reward);
+            rewards.push(&Reward {
+                amount: input_reward,
+                label: reward_labels.next(),
+            });
         }
 
-        let mut participants = Vector::new(StorageKey::EventParticipants { event_id });
-        for input_participant in event_input.participants {
-            participants.push(&input_participant);
+        let mut nft_rewards = Vector::new(StorageKey::EventNftRewards { event_id });
+        if let Some(nft_reward) = nft_reward {
+            nft_rewards.push(&nft_reward);
         }
 
-        let event = Event {
+        let mut event = Event {
             status: EventStatus::Pending,
             owner_account_id: owner_id,
             rewards,
             rewards_token_id: event_input.rewards_token_id,
-            participants,
+            nft_rewards,
+            participants: Vector::new(StorageKey::EventParticipants { event_id }),
+            participant_indices: LookupMap::new(StorageKey::EventParticipantIndices { event_id }),
+            tickets: Tickets::new(StorageKey::EventTickets { event_id }, StorageKey::EventTicketsTree { event_id }),
             allow_duplicate_participants: event_input.allow_duplicate_participants,
+            open_registration: event_input.open_registration,
+            ticket_price: event_input.ticket_price.map(|ticket_price| ticket_price.0),
+            pool_tiers,
+            prize_pool: 0,
+            max_participants: event_input.max_participants.map(|max_participants| max_participants.0),
+            reserve_winners_number,
+            reserve_winners: Vector::new(StorageKey::EventReserveWinners { event_id }),
+            used_reserve_winners: 0,
+            claim_period: event_input.claim_period.map(|claim_period| claim_period.0),
+            payout_backend: event_input.payout_backend,
+            keeper_tip,
+            keeper_account_id: None,
+            keeper_tip_paid: false,
 
             add_participants_start_timestamp: event_input.add_participants_start_timestamp,
             add_participants_end_timestamp: event_input.add_participants_end_timestamp,
             event_timestamp: event_input.event_timestamp,
             finalized_timestamp: None,
+            draw: None,
 
             title: event_input.title,
             description: event_input.description,
         };
+        event.add_participants(event_input.participants, event_input.participant_tickets);
         self.events.insert(&event_id, &event);
         self.next_event_id += 1;
 
-        event_id
+        (event_id, tokens - payment)
     }
-
-    pub fn insert_participants(&mut self, event_id: u64, participants: Vec<AccountId>) {
+    /// Sends pending payouts from `from_index` in one batch. The batch grows while the estimated gas of its transfers
+    /// fits into the prepaid gas without `reserved_gas`. Returns the batch promise, None without pending payouts,
+    /// and the index of the first payout left for the next batch
+    pub(crate) fn internal_distribute_payouts(
+        &mut self,
+        event_id: u64,
+        from_index: u64,
+        limit: Option<u64>,
+        reserved_gas: Gas,
+    ) -> (Option<Promise>, Option<u64>) {
         self.assert_active();
-        let mut event: Event = self.internal_get_event(&event_id);
-
-        assert_eq!(event.status, EventStatus::Pending, "Already finalized");
-
+        let event: Event = self.internal_get_event(&event_id);
+        assert!(
+            matches!(event.status, EventStatus::Calculated | EventStatus::Cancelled),
+            "Distribution is not available"
+        );
 
-        let current_timestamp: Duration = env::block_timestamp();
-        assert!(current_timestamp >= event.add_participants_start_timestamp.0, "It's too early to add participants");
-        assert!(current_timestamp < event.add_participants_end_timestamp.0, "It's too late to add participants");
-        assert!(current_timestamp < event.event_timestamp.0, "Event date already passed");
+        let batch_id = self.internal_next_payout_batch_id();
+        let mut accounts: Vec<MultisenderPayout> = [].to_vec();
+        let mut multisend_indices: Vec<PayoutIndex> = vec![];
+        let mut total: Balance = 0;
+        // Separate token transfers, sent alongside the multisender batch
+        let mut promises: Vec<Promise> = vec![];
+        let mut promises_gas: Gas = Gas(0);
 
-        self.assert_event_owner(&event);
+        let payout_backend = event.payout_backend.unwrap_or(self.payout_backend);
+        let mut payouts = self.internal_get_payouts(&event_id);
+        let limit = limit.unwrap_or(payouts.len() as u64);
+        let available_gas = env::prepaid_gas().0
+            .saturating_sub(env::used_gas().0)
+            .saturating_sub(BASE_PAYOUT_PREPARATION_GAS.0)
+            .saturating_sub(reserved_gas.0);
+        let mut batch_gas: u64 = 0;
+        let mut sent: u64 = 0;
+        let mut next_index: Option<u64> = None;
+
+        for payout_index in from_index..payouts.len() as u64 {
+            let index = payout_index as usize;
+            let payout = &payouts[index];
+            // Prizes of claim mode events are pulled by winners with `claim`
+            if payout.status != PayoutStatus::Pending || payout.claim_deadline.is_some() {
+                continue;
+            }
 
-        for participant in participants {
-            if event.allow_duplicate_participants || event.participants.to_vec().contains(&participant) {
-                event.participants.push(&participant);
+            // Payouts of a failed multisender batch are sent one by one, so every failure is counted against its own payout
+            let multisend = payout.nft.is_none() && payout.token_id.is_none() && payout_backend == PayoutBackend::Multisender
+                && payout.batch_outcome != Some(PayoutBatchOutcome::Failure);
+            let payout_gas = GAS_PER_PAYOUT.0 + if payout.nft.is_some() {
+                GAS_FOR_NFT_TRANSFER.0 + GAS_FOR_AFTER_PAYOUT_TRANSFER.0
+            } else if payout.token_id.is_some() {
+                GAS_FOR_FT_TRANSFER.0 + GAS_FOR_AFTER_PAYOUT_TRANSFER.0
+            } else if !multisend {
+                GAS_FOR_AFTER_PAYOUT_TRANSFER.0
+            } else if accounts.is_empty() {
+                GAS_FOR_MULTISEND.0 + GAS_FOR_AFTER_MULTISEND.0 + GAS_FOR_MULTISEND_PER_ACCOUNT.0
+            } else {
+                GAS_FOR_MULTISEND_PER_ACCOUNT.0
+            };
+            if sent == limit || batch_gas + payout_gas > available_gas {
+                next_index = Some(payout_index);
+                break;
+            }
+            batch_gas += payout_gas;
+            sent += 1;
+
+            if let Some(nft_reward) = &payout.nft {
+                promises.push(nft_transfer_payout(event_id, batch_id, payout_index, nft_reward, payout));
+                promises_gas = promises_gas + GAS_FOR_NFT_TRANSFER + GAS_FOR_AFTER_PAYOUT_TRANSFER;
+            } else if let Some(token_id) = &payout.token_id {
+                promises.push(ft_transfer_payout(event_id, batch_id, payout_index, token_id, payout));
+                promises_gas = promises_gas + GAS_FOR_FT_TRANSFER + GAS_FOR_AFTER_PAYOUT_TRANSFER;
+            } else if !multisend {
+                promises.push(near_transfer_payout(event_id, batch_id, payout_index, payout));
+                promises_gas += GAS_FOR_AFTER_PAYOUT_TRANSFER;
+            } else {
+                accounts.push({
+                    MultisenderPayout {
+                        account_id: payout.account_id.to_owned(),
+                        token_id: None,
+                        amount: payout.amount,
+                    }
+                });
+                multisend_indices.push(payout_index);
+                total += payout.amount.0;
             }
+            payouts[index].status = PayoutStatus::Complete;
+            payouts[index].batch_id = Some(batch_id);
+            payouts[index].batch_outcome = None;
+        }
+        assert!(sent > 0 || next_index.is_none(), "Not enough gas to send a payout");
+        if sent == 0 {
+            log!("No pending payouts");
+            return (None, None);
         }
 
-        self.events.insert(&event_id, &event);
-    }
+        self.payouts.insert(&event_id, &payouts);
 
-    pub fn finalize_event(&mut self, event_id: u64) {
-        self.assert_active();
-        let mut event: Event = self.internal_get_event(&event_id);
-        assert_eq!(event.status, EventStatus::Pending, "Already finalized");
-        assert!(!event.rewards.is_empty(), "Rewards Missing");
-        assert!(!event.participants.is_empty(), "Participants Missing");
+        log!("Distributing rewards in batch {}: {}", batch_id, total);
+
+        if !accounts.is_empty() {
+            // The multisender gets all gas which isn't reserved for other transfers and callbacks
+            let unspent_gas = Gas(available_gas - promises_gas.0 - GAS_FOR_AFTER_MULTISEND.0 - sent * GAS_PER_PAYOUT.0);
+
+            promises.push(ext_multisender::multisend_attached_tokens(
+                accounts,
+                self.multisender_contract.to_owned(),
+                total,
+                unspent_gas)
+            .then(ext_self::after_multisend_attached_tokens(
+                event_id,
+                batch_id,
+                multisend_indices,
+                env::current_account_id(),
+                NO_DEPOSIT,
+                GAS_FOR_AFTER_MULTISEND,
+            )));
+        }
 
-        assert!(
-            env::block_timestamp() >= event.event_timestamp.0,
-            "It's too early to finalize the event. Please wait for block {}",
-            event.event_timestamp.0
-        );
+        let promise = promises.into_iter()
+            .reduce(|promises, promise| promises.and(promise));
+        (promise, next_index)
+    }
+}
 
-        event.status = EventStatus::Calculated;
-        event.finalized_timestamp = Some(env::block_timestamp().into());
-        self.events.insert(&event_id, &event);
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
 
-        // Total number of distributed payouts
-        let mut payouts_number: PayoutIndex = 0;
-        let mut winners: Vec<AccountId> = vec![];
-        let max_participants: usize = event.participants.len() as usize;
-        let max_rewards: PayoutIndex = event.rewards.len() as PayoutIndex;
-        let mut index = 0;
-        let seed = near_sdk::env::random_seed();
-        let mut payouts = self.internal_get_payouts(&event_id);
-        for reward in event.rewards.to_vec() {
-            let mut winner_account_id: Option<AccountId> = None;
-
-            while winner_account_id.is_none() || winners.contains(&winner_account_id.clone().unwrap()) {
-                if winner_account_id.is_some() {
-                    index += 1;
-                } else if winners.len() >= max_participants {
-                    log!("All participants got their prizes");
-                    winner_account_id = None;
-                    break;
-                }
-                let winner_index: u64 = u64::from(seed[index]) % (max_participants as u64);
-                winner_account_id = Some(event.participants.get(winner_index).unwrap());
+    use super::*;
 
-                // TODO check this
-                if index >= MAX_GIVEAWAY_WINNERS {
-                    index = 0;
-                    log!("Reset index");
-                }
-            }
+    pub(crate) const ONE_NEAR: Balance = 1_000_000_000_000_000_000_000_000;
 
-            if let Some(winner_account_id_value) = winner_account_id {
-                winners.push(winner_account_id_value.clone());
+    /// Drawn account, place and refund flag of every payout
+    type DrawnPayouts = Vec<(AccountId, u64, bool)>;
 
-                payouts.push(Payout {
-                    account_id: winner_account_id_value.clone(),
-                    amount: reward,
-                    token_id: event.rewards_token_id.to_owned(),
-                    status: PayoutStatus::Pending,
-                });
+    pub(crate) fn contract_account_id() -> AccountId {
+        AccountId::new_unchecked("giveaway.near".to_string())
+    }
 
-                payouts_number += 1;
+    pub(crate) fn get_context(predecessor_account_id: AccountId, block_timestamp: Duration) -> VMContextBuilder {
+        let mut builder = VMContextBuilder::new();
+        builder
+            .current_account_id(contract_account_id())
+            .predecessor_account_id(predecessor_account_id)
+            .block_timestamp(block_timestamp);
+        builder
+    }
 
-                log!("@{} won reward of {} yNEAR", winner_account_id_value, reward.0);
-            }
+    pub(crate) fn set_context(predecessor_account_id: AccountId, attached_deposit: Balance) {
+        testing_env!(get_context(predecessor_account_id, 0).attached_deposit(attached_deposit).build());
+    }
 
-            if payouts_number >= max_rewards {
-                break;
-            }
+    /// Context of a callback which receives `promise_result` of the transfer
+    pub(crate) fn set_promise_result(promise_result: PromiseResult) {
+        testing_env!(
+            get_context(contract_account_id(), 0).build(),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![promise_result]
+        );
+    }
 
-            index += 1;
-            if index >= MAX_GIVEAWAY_WINNERS {
-                index = 0;
-                log!("Reset index");
-            }
+    /// NEAR event with participants accounts(2..) holding `tickets`
+    pub(crate) fn get_event_input(rewards_number: u64, tickets: &[TicketsNumber], reserve_winners_number: u64) -> EventInput {
+        EventInput {
+            rewards: vec![U128(ONE_NEAR); rewards_number as usize],
+            reward_labels: None,
+            rewards_token_id: None,
+            participants: (0..tickets.len()).map(|index| accounts(index + 2)).collect(),
+            participant_tickets: Some(tickets.iter().map(|count| U64(*count)).collect()),
+            allow_duplicate_participants: true,
+            open_registration: false,
+            max_participants: None,
+            ticket_price: None,
+            pool_tiers: None,
+            reserve_winners_number: Some(U64(reserve_winners_number)),
+            claim_period: None,
+            payout_backend: None,
+            keeper_tip: None,
+            add_participants_start_timestamp: U64(0),
+            add_participants_end_timestamp: U64(0),
+            event_timestamp: U64(0),
+            title: "Giveaway".to_string(),
+            description: "".to_string(),
         }
-
-        self.payouts.insert(&event_id, &payouts);
     }
 
-    pub fn distribute_payouts(&mut self, event_id: u64, from_index: Option<u64>, limit: Option<u64>) -> Promise {
-        self.assert_active();
-        let event: Event = self.internal_get_event(&event_id);
-        assert_eq!(event.status, EventStatus::Calculated, "Distribution is not available");
+    /// Creates the contract of accounts(0) and the event of accounts(1)
+    pub(crate) fn add_event(event_input: EventInput) -> (Giveaway, EventId) {
+        set_context(accounts(0), 0);
+        let mut contract = Giveaway::new(accounts(0), None);
 
-        let mut accounts: Vec<MultisenderPayout> = [].to_vec();
-        let mut total: Balance = 0;
+        let rewards: Balance = event_input.rewards.iter().map(|reward| reward.0).sum();
+        let keeper_tip: Balance = event_input.keeper_tip.map(|keeper_tip| keeper_tip.0).unwrap_or(0);
+        set_context(accounts(1), rewards + keeper_tip + ONE_NEAR);
+        let event_id = contract.add_event(event_input);
+        (contract, event_id)
+    }
 
-        let from_index = from_index.unwrap_or_default();
-        let limit = limit.unwrap_or_else(|| std::cmp::min(event.rewards.len(), event.participants.len()));
+    pub(crate) fn setup(rewards_number: u64, tickets: &[TicketsNumber], reserve_winners_number: u64) -> (Giveaway, EventId) {
+        add_event(get_event_input(rewards_number, tickets, reserve_winners_number))
+    }
 
-        let mut payouts = self.internal_get_payouts(&event_id);
+    fn get_drawn_payouts(contract: &Giveaway, event_id: EventId) -> DrawnPayouts {
+        contract.internal_get_payouts(&event_id).into_iter()
+            .map(|payout| (payout.account_id, payout.place, payout.refund))
+            .collect()
+    }
 
-        for index  in from_index..from_index + limit {
-            let index = index as usize;
-            if let Some(payout) = payouts.get(index) {
-                if payout.status == PayoutStatus::Pending {
-                    accounts.push({
-                        MultisenderPayout {
-                            account_id: payout.account_id.to_owned(),
-                            token_id: None,
-                            amount: payout.amount,
-                        }
-                    });
-                    total += payout.amount.0;
-                    payouts[index].status = PayoutStatus::Complete;
-                }
-            }
-        }
+    /// Finalizes the event with `limit` and returns its payouts, reserve winners and draw counter.
+    /// Runs in a separate thread, which has its own mocked storage
+    fn finalize(limit: Option<u64>) -> (DrawnPayouts, Vec<AccountId>, u64) {
+        std::thread::spawn(move || {
+            let (mut contract, event_id) = setup(3, &[1, 3, 2, 5], 1);
+            set_context(accounts(0), 0);
+            while !contract.finalize_event(event_id, limit) {}
+
+            let event = contract.internal_get_event(&event_id);
+            assert_eq!(event.status, EventStatus::Calculated);
+            let counter = event.draw.as_ref().expect("ERR_NO_DRAW").counter;
+            (get_drawn_payouts(&contract, event_id), event.reserve_winners.to_vec(), counter)
+        }).join().expect("Finalization failed")
+    }
 
-        self.payouts.insert(&event_id, &payouts);
+    #[test]
+    fn test_finalize_event_in_batches() {
+        let (payouts, reserve_winners, counter) = finalize(None);
+        assert_eq!(payouts.len(), 3);
+        assert_eq!(reserve_winners.len(), 1);
+        assert!(payouts.iter().all(|(_, _, refund)| !refund));
 
-        log!("Distributing rewards: {}", total);
-
-        let unspent_gas = env::prepaid_gas() - BASE_PAYOUT_PREPARATION_GAS - GAS_FOR_AFTER_MULTISEND;
-
-        ext_multisender::multisend_attached_tokens(
-            accounts.clone(),
-            self.multisender_contract.to_owned(),
-            total,
-            unspent_gas)
-       .then(ext_self::after_multisend_attached_tokens(
-           event_id,
-           accounts,
-           env::current_account_id(),
-           NO_DEPOSIT,
-           GAS_FOR_AFTER_MULTISEND,
-       ))
+        assert_eq!(finalize(Some(1)), (payouts.clone(), reserve_winners.clone(), counter));
+        assert_eq!(finalize(Some(3)), (payouts, reserve_winners, counter));
     }
 
-    pub fn close_event(&mut self, event_id: u64) {
-        self.assert_active();
-        let mut event: Event = self.internal_get_event(&event_id);
-        assert_eq!(event.status, EventStatus::Calculated, "Method is not available");
-
-        let payouts = self.internal_get_payouts(&event_id);
+    #[test]
+    fn test_finalize_event_status() {
+        let (mut contract, event_id) = setup(3, &[1, 1, 1, 1], 0);
+        set_context(accounts(5), 0);
+
+        assert!(!contract.finalize_event(event_id, Some(2)));
+        let event = contract.internal_get_event(&event_id);
+        assert_eq!(event.status, EventStatus::Finalizing);
+        assert_eq!(event.draw.as_ref().expect("ERR_NO_DRAW").cursor, 2);
+        assert!(event.finalized_timestamp.is_none());
+        assert_eq!(contract.internal_get_payouts(&event_id).len(), 2);
+
+        assert!(contract.finalize_event(event_id, Some(2)));
+        let event = contract.internal_get_event(&event_id);
+        assert_eq!(event.status, EventStatus::Calculated);
+        assert!(event.finalized_timestamp.is_some());
+        assert_eq!(event.keeper_account_id, Some(accounts(5)));
+        assert_eq!(contract.internal_get_payouts(&event_id).len(), 3);
+    }
 
-        let rewards_num = std::cmp::min(event.rewards.len(), event.participants.len());
-        for index in 0..rewards_num {
-            let index = index as usize;
-            assert_eq!(payouts[index].status, PayoutStatus::Complete, "Payouts still pending");
-        }
+    #[test]
+    #[should_panic(expected = "Already finalized")]
+    fn test_finalize_calculated_event() {
+        let (mut contract, event_id) = setup(1, &[1, 1], 0);
+        set_context(accounts(0), 0);
+        assert!(contract.finalize_event(event_id, None));
+        contract.finalize_event(event_id, None);
+    }
 
-        log!("All payouts distributed");
-        event.status = EventStatus::Distributed;
-        self.events.insert(&event_id, &event);
+    #[test]
+    fn test_finalize_event_refunds_unawarded_prizes() {
+        let (mut contract, event_id) = setup(4, &[3, 1], 2);
+        set_context(accounts(0), 0);
+        assert!(contract.finalize_event(event_id, None));
+
+        let payouts = get_drawn_payouts(&contract, event_id);
+        let winners: Vec<AccountId> = payouts[..2].iter().map(|(account_id, _, _)| account_id.clone()).collect();
+        assert!(winners.contains(&accounts(2)) && winners.contains(&accounts(3)));
+        assert!(payouts[..2].iter().all(|(_, _, refund)| !refund));
+        // Every participant wins once, the rest returns to the owner and no reserve winners are left
+        assert_eq!(payouts[2..], [(accounts(1), 3, true), (accounts(1), 4, true)]);
+        let event = contract.internal_get_event(&event_id);
+        assert_eq!(event.status, EventStatus::Calculated);
+        assert!(event.reserve_winners.is_empty());
     }
 }
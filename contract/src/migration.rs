@@ -0,0 +1,301 @@
+use near_sdk::collections::{LookupSet, UnorderedSet};
+
+use crate::*;
+
+/// Contract state of the first release
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct GiveawayV1 {
+   pub owner_id: AccountId,
+   pub active: bool,
+   pub next_event_id: u64,
+   pub events: UnorderedMap<EventId, EventV1>,
+   pub payouts: UnorderedMap<EventId, Vec<PayoutV1>>,
+   pub whitelisted_tokens: LookupSet<TokenId>,
+   pub multisender_contract: AccountId,
+   pub total_service_fee: UnorderedMap<Option<TokenId>, Balance>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct EventV1 {
+   pub owner_account_id: AccountId,
+   pub status: EventStatusV1,
+   pub rewards: UnorderedSet<WrappedBalance>,
+   pub rewards_token_id: Option<TokenId>,
+   pub participants: Vector<AccountId>,
+   pub allow_duplicate_participants: bool,
+   pub add_participants_start_timestamp: WrappedDuration,
+   pub add_participants_end_timestamp: WrappedDuration,
+   pub event_timestamp: WrappedDuration,
+   pub finalized_timestamp: Option<WrappedDuration>,
+   pub title: String,
+   pub description: String,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub enum EventStatusV1 {
+   Pending,
+   Calculated,
+   Distributed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct PayoutV1 {
+   pub account_id: AccountId,
+   pub amount: WrappedBalance,
+   pub token_id: Option<TokenId>,
+   /// `Pending` and `Complete` keep their tags in `PayoutStatus`
+   pub status: PayoutStatus,
+}
+
+/// Events and payouts of the first release which aren't converted yet, at their old storage prefixes
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct LegacyState {
+   pub events: UnorderedMap<EventId, EventV1>,
+   pub payouts: UnorderedMap<EventId, Vec<PayoutV1>>,
+}
+
+impl From<EventStatusV1> for EventStatus {
+   fn from(status: EventStatusV1) -> Self {
+      match status {
+         EventStatusV1::Pending => EventStatus::Pending,
+         EventStatusV1::Calculated => EventStatus::Calculated,
+         EventStatusV1::Distributed => EventStatus::Distributed,
+      }
+   }
+}
+
+/// Rewards and participants move to the collections of the current layout under the same prefixes,
+/// every listed participant gets a ticket
+fn migrate_event(event_id: EventId, mut old_event: EventV1) -> Event {
+   let old_rewards = old_event.rewards.to_vec();
+   old_event.rewards.clear();
+   let old_participants = old_event.participants.to_vec();
+   old_event.participants.clear();
+
+   let mut event = Event {
+      owner_account_id: old_event.owner_account_id,
+      status: old_event.status.into(),
+      rewards: Vector::new(StorageKey::EventRewards { event_id }),
+      rewards_token_id: old_event.rewards_token_id,
+      nft_rewards: Vector::new(StorageKey::EventNftRewards { event_id }),
+      participants: Vector::new(StorageKey::EventParticipants { event_id }),
+      participant_indices: LookupMap::new(StorageKey::EventParticipantIndices { event_id }),
+      tickets: Tickets::new(StorageKey::EventTickets { event_id }, StorageKey::EventTicketsTree { event_id }),
+      allow_duplicate_participants: old_event.allow_duplicate_participants,
+      open_registration: false,
+      max_participants: None,
+      ticket_price: None,
+      pool_tiers: vec![],
+      prize_pool: 0,
+      reserve_winners_number: 0,
+      reserve_winners: Vector::new(StorageKey::EventReserveWinners { event_id }),
+      used_reserve_winners: 0,
+      claim_period: None,
+      payout_backend: None,
+      keeper_tip: 0,
+      keeper_account_id: None,
+      keeper_tip_paid: false,
+      add_participants_start_timestamp: old_event.add_participants_start_timestamp,
+      add_participants_end_timestamp: old_event.add_participants_end_timestamp,
+      event_timestamp: old_event.event_timestamp,
+      finalized_timestamp: old_event.finalized_timestamp,
+      draw: None,
+      title: old_event.title,
+      description: old_event.description,
+   };
+   for amount in old_rewards {
+      event.rewards.push(&Reward { amount, label: None });
+   }
+   // Repeated participants of events with duplicates become extra tickets
+   for account_id in old_participants {
+      event.add_participant(&account_id, 1);
+   }
+   event
+}
+
+fn migrate_payouts(old_payouts: Vec<PayoutV1>) -> Vec<Payout> {
+   old_payouts.into_iter().enumerate()
+      .map(|(payout_index, old_payout)| Payout {
+         status: old_payout.status,
+         ..Payout::new(
+            old_payout.account_id,
+            old_payout.amount,
+            old_payout.token_id,
+            None,
+            payout_index as u64 + 1,
+            None,
+            false,
+         )
+      })
+      .collect()
+}
+
+#[near_bindgen]
+impl Giveaway {
+   /// Upgrades the state of the first release. Metadata of its whitelisted tokens isn't stored, so it's passed here.
+   /// Events are converted later with `migrate_events`, the contract stays paused until the owner resumes it with `set_active`
+   #[private]
+   #[init(ignore_state)]
+   pub fn migrate(whitelisted_tokens: Vec<WhitelistedTokenOutput>) -> Self {
+      let mut old_state: GiveawayV1 = env::state_read().expect("ERR_NO_STATE");
+
+      let mut contract = Self::new(old_state.owner_id, Some(old_state.multisender_contract));
+      contract.active = false;
+      contract.next_event_id = old_state.next_event_id;
+      // The old maps keep the `Events` and `Payouts` prefixes until their events are converted
+      contract.events = UnorderedMap::new(StorageKey::MigratedEvents);
+      contract.payouts = UnorderedMap::new(StorageKey::MigratedPayouts);
+
+      for token in whitelisted_tokens {
+         assert!(old_state.whitelisted_tokens.remove(&token.token_id), "ERR_TOKEN_NOT_FOUND");
+         contract.whitelisted_tokens.insert(&token.token_id, &token.metadata);
+      }
+      for (token_id, service_fee) in old_state.total_service_fee.iter() {
+         contract.total_service_fee.insert(&token_id, &service_fee);
+      }
+      old_state.total_service_fee.clear();
+
+      log!("{} events to migrate", old_state.events.len());
+      contract.migration = Some(LegacyState {
+         events: old_state.events,
+         payouts: old_state.payouts,
+      });
+      contract
+   }
+
+   /// Converts up to `limit` events of the first release with their payouts. Returns the number of events left
+   pub fn migrate_events(&mut self, limit: u64) -> u64 {
+      self.assert_contract_owner();
+      let mut legacy = self.migration.take().expect("Nothing to migrate");
+
+      let event_ids: Vec<EventId> = legacy.events.keys_as_vector().iter().take(limit as usize).collect();
+      for event_id in event_ids {
+         let old_event = legacy.events.remove(&event_id).expect("ERR_NO_EVENT");
+         self.events.insert(&event_id, &migrate_event(event_id, old_event));
+         if let Some(old_payouts) = legacy.payouts.remove(&event_id) {
+            self.payouts.insert(&event_id, &migrate_payouts(old_payouts));
+         }
+      }
+
+      let events_left = legacy.events.len();
+      if events_left > 0 {
+         self.migration = Some(legacy);
+      } else {
+         log!("All events migrated");
+      }
+      events_left
+   }
+
+   pub fn is_migrated(&self) -> bool {
+      self.migration.is_none()
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use near_sdk::test_utils::accounts;
+
+   use crate::tests::*;
+
+   use super::*;
+
+   fn get_old_event(status: EventStatusV1, rewards: &[Balance], participants: &[AccountId], event_id: EventId) -> EventV1 {
+      let mut old_rewards = UnorderedSet::new(StorageKey::EventRewards { event_id });
+      rewards.iter().for_each(|reward| { old_rewards.insert(&U128(*reward)); });
+      let mut old_participants = Vector::new(StorageKey::EventParticipants { event_id });
+      participants.iter().for_each(|account_id| old_participants.push(account_id));
+      EventV1 {
+         owner_account_id: accounts(1),
+         status,
+         rewards: old_rewards,
+         rewards_token_id: None,
+         participants: old_participants,
+         allow_duplicate_participants: true,
+         add_participants_start_timestamp: U64(0),
+         add_participants_end_timestamp: U64(0),
+         event_timestamp: U64(0),
+         finalized_timestamp: None,
+         title: "Giveaway".to_string(),
+         description: "".to_string(),
+      }
+   }
+
+   /// Writes the state of the first release with a calculated and a pending event
+   fn write_old_state() {
+      set_context(accounts(0), 0);
+      let token_id: TokenId = AccountId::new_unchecked("token.near".to_string());
+      // Prefixes of the first release
+      let mut old_state = GiveawayV1 {
+         owner_id: accounts(0),
+         active: true,
+         next_event_id: 2,
+         events: UnorderedMap::new(StorageKey::Events),
+         payouts: UnorderedMap::new(StorageKey::Payouts),
+         whitelisted_tokens: LookupSet::new(vec![4]),
+         multisender_contract: accounts(5),
+         total_service_fee: UnorderedMap::new(vec![5]),
+      };
+      old_state.whitelisted_tokens.insert(&token_id);
+      old_state.total_service_fee.insert(&None, &ONE_NEAR);
+
+      let participants = [accounts(2), accounts(3), accounts(2)];
+      old_state.events.insert(&0, &get_old_event(EventStatusV1::Calculated, &[2 * ONE_NEAR, ONE_NEAR], &participants, 0));
+      old_state.payouts.insert(&0, &vec![
+         PayoutV1 { account_id: accounts(2), amount: U128(2 * ONE_NEAR), token_id: None, status: PayoutStatus::Complete },
+         PayoutV1 { account_id: accounts(3), amount: U128(ONE_NEAR), token_id: None, status: PayoutStatus::Pending },
+      ]);
+      old_state.events.insert(&1, &get_old_event(EventStatusV1::Pending, &[ONE_NEAR], &[accounts(4)], 1));
+      env::state_write(&old_state);
+   }
+
+   #[test]
+   fn test_migrate() {
+      write_old_state();
+      let mut contract = Giveaway::migrate(vec![WhitelistedTokenOutput {
+         token_id: AccountId::new_unchecked("token.near".to_string()),
+         metadata: WhitelistedToken {
+            symbol: "TOKEN".to_string(),
+            decimals: 18,
+            min_deposit: U128(1),
+            storage_deposit: U128(0),
+         },
+      }]);
+      assert!(!contract.is_active());
+      assert_eq!(contract.get_next_event_id(), 2);
+      assert_eq!(contract.get_multisender_contract(), accounts(5));
+      assert_eq!(contract.get_total_service_fee(None), Some(ONE_NEAR));
+      assert!(contract.is_whitelisted_token(&AccountId::new_unchecked("token.near".to_string())));
+
+      assert_eq!(contract.migrate_events(1), 1);
+      assert!(!contract.is_migrated());
+      assert_eq!(contract.migrate_events(1), 0);
+      assert!(contract.is_migrated());
+
+      let event = contract.internal_get_event(&0);
+      assert_eq!(event.status, EventStatus::Calculated);
+      let rewards: Vec<Balance> = event.rewards.iter().map(|reward| reward.amount.0).collect();
+      assert_eq!(rewards, vec![2 * ONE_NEAR, ONE_NEAR]);
+      assert_eq!(event.participants.to_vec(), vec![accounts(2), accounts(3)]);
+      assert_eq!(event.tickets.get(0), 2);
+      assert_eq!(event.tickets.get_total(), 3);
+      let payouts = contract.internal_get_payouts(&0);
+      assert_eq!(payouts.iter().map(|payout| payout.place).collect::<Vec<u64>>(), vec![1, 2]);
+      assert_eq!(payouts[0].status, PayoutStatus::Complete);
+      assert_eq!(payouts[1].status, PayoutStatus::Pending);
+      assert_eq!(payouts[1].account_id, accounts(3));
+
+      // Converted events work as usual after the owner resumes the contract
+      contract.set_active(true);
+      assert_eq!(contract.internal_get_event(&1).status, EventStatus::Pending);
+      assert!(contract.finalize_event(1, None));
+      assert_eq!(contract.internal_get_payouts(&1)[0].account_id, accounts(4));
+   }
+
+   #[test]
+   #[should_panic(expected = "Events are not migrated yet")]
+   fn test_resume_before_events_migrated() {
+      write_old_state();
+      let mut contract = Giveaway::migrate(vec![]);
+      contract.set_active(true);
+   }
+}
@@ -1,6 +1,5 @@
-use near_sdk::PromiseResult;
-
 use crate::*;
+use crate::utils::is_promise_success;
 
 #[ext_contract(ext_multisender)]
 pub trait ExtMultisender {
@@ -18,33 +17,10 @@ pub struct MultisenderPayout {
 #[near_bindgen]
 impl Giveaway {
    #[private]
-   pub fn after_multisend_attached_tokens(&mut self, event_id: u64, accounts: Vec<MultisenderPayout>) -> bool {
+   pub fn after_multisend_attached_tokens(&mut self, event_id: u64, batch_id: PayoutBatchId, payout_indices: Vec<PayoutIndex>) -> bool {
       let promise_success = is_promise_success();
-      if !promise_success {
-         let mut payouts = self.internal_get_payouts(&event_id);
-         for account in accounts {
-            payouts = payouts.into_iter().map(|mut payout| {
-               if payout.account_id == account.account_id && payout.amount == account.amount {
-                  payout.status = PayoutStatus::Pending;
-               }
-               payout
-            }).collect()
-         }
-         self.payouts.insert(&event_id, &payouts);
-      }
+      self.internal_record_batch_outcome(event_id, batch_id, &payout_indices, promise_success, false);
 
       promise_success
    }
 }
-
-fn is_promise_success() -> bool {
-   assert_eq!(
-      env::promise_results_count(),
-      1,
-      "Contract expected a result on the callback"
-   );
-   match env::promise_result(0) {
-      PromiseResult::Successful(_) => true,
-      _ => false,
-   }
-}
@@ -0,0 +1,90 @@
+use crate::*;
+
+pub type NftTokenId = String;
+
+#[derive(Debug, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NftReward {
+   pub contract_id: AccountId,
+   pub token_id: NftTokenId,
+   /// prize tier, e.g. "Grand prize"
+   pub label: Option<String>,
+}
+
+/// `msg` of nft_transfer_call
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(untagged)]
+pub enum NftTransferMessage {
+   /// Add NFT prize to the existing pending event
+   AddReward { event_id: EventId, label: Option<String> },
+   /// Create a new event with NFT prize
+   AddEvent(Box<EventInput>),
+}
+
+#[ext_contract(ext_nft)]
+pub trait ExtNonFungibleToken {
+   fn nft_transfer(&mut self, receiver_id: AccountId, token_id: NftTokenId, approval_id: Option<u64>, memo: Option<String>);
+}
+
+/// Sends an NFT payout of the batch and marks it `Failed` in the callback if the transfer failed
+pub(crate) fn nft_transfer_payout(event_id: EventId, batch_id: PayoutBatchId, payout_index: PayoutIndex, nft_reward: &NftReward, payout: &Payout) -> Promise {
+   ext_nft::nft_transfer(
+      payout.account_id.to_owned(),
+      nft_reward.token_id.to_owned(),
+      None,
+      Some(format!("Giveaway #{} reward", event_id)),
+      nft_reward.contract_id.to_owned(),
+      ONE_YOCTO,
+      GAS_FOR_NFT_TRANSFER)
+      .then(ext_self::after_payout_transfer(
+         event_id,
+         batch_id,
+         payout_index,
+         env::current_account_id(),
+         NO_DEPOSIT,
+         GAS_FOR_AFTER_PAYOUT_TRANSFER,
+      ))
+}
+
+#[near_bindgen]
+impl Giveaway {
+   /// Escrows an NFT prize. `msg` is either `{"event_id": .., "label": ..}` to add a prize to a pending event
+   /// of `previous_owner_id` or a JSON-serialized `EventInput` without fungible rewards to create a new event.
+   /// Only tokens of whitelisted NFT contracts are accepted.
+   /// Returns false since the token is always kept, failures revert the transfer
+   pub fn nft_on_transfer(&mut self, sender_id: AccountId, previous_owner_id: AccountId, token_id: NftTokenId, msg: String) -> PromiseOrValue<bool> {
+      self.assert_active();
+      self.assert_whitelisted_nft_contract(&env::predecessor_account_id());
+      let mut nft_reward = NftReward {
+         contract_id: env::predecessor_account_id(),
+         token_id,
+         label: None,
+      };
+      let message: NftTransferMessage = near_sdk::serde_json::from_str(&msg).expect("ERR_WRONG_MSG_FORMAT");
+
+      match message {
+         NftTransferMessage::AddReward { event_id, label } => {
+            if let Some(label) = label {
+               assert_valid_reward_label(&label);
+               nft_reward.label = Some(label);
+            }
+
+            let mut event: Event = self.internal_get_event(&event_id);
+            assert_eq!(event.status, EventStatus::Pending, "Already finalized");
+            assert_eq!(previous_owner_id, event.owner_account_id, "ERR_NO_ACCESS");
+            assert!(event.get_prizes_number() + 1 < MAX_GIVEAWAY_WINNERS as u64, "Too many rewards");
+
+            event.nft_rewards.push(&nft_reward);
+            self.events.insert(&event_id, &event);
+            log!("@{} added NFT {} of {} to event {}", sender_id, nft_reward.token_id, nft_reward.contract_id, event_id);
+         }
+         NftTransferMessage::AddEvent(event_input) => {
+            let (event_id, _) = self.internal_add_event(previous_owner_id.clone(), *event_input, 0, Some(nft_reward));
+            log!("@{} added event {}", previous_owner_id, event_id);
+         }
+      }
+
+      PromiseOrValue::Value(false)
+   }
+}
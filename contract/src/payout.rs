@@ -1,6 +1,9 @@
 use crate::*;
+use crate::utils::is_promise_success;
 
 pub type PayoutIndex = u64;
+/// Id of a distribution or claim call, shared by all payouts it sends
+pub type PayoutBatchId = u64;
 
 /// Payout information
 #[derive(BorshSerialize, BorshDeserialize)]
@@ -14,7 +17,81 @@ pub struct Payout {
    pub account_id: AccountId,
    pub amount: WrappedBalance,
    pub token_id: Option<TokenId>,
-   pub status: PayoutStatus
+   /// NFT prize, `amount` is zero for such payouts
+   pub nft: Option<NftReward>,
+   /// 1-based position of the prize in the event
+   pub place: u64,
+   /// prize tier of the reward
+   pub label: Option<String>,
+   /// unawarded prize returned to the event owner
+   pub refund: bool,
+   pub status: PayoutStatus,
+   /// previous receivers of the prize, the first one is the drawn winner
+   pub redraws: Vec<PayoutRedraw>,
+   /// end of the claim period in claim mode events, the winner pulls the prize with `claim` before it
+   pub claim_deadline: Option<WrappedDuration>,
+   /// last batch which sent the payout
+   pub batch_id: Option<PayoutBatchId>,
+   /// result of the last batch, None while the transfer is in progress
+   pub batch_outcome: Option<PayoutBatchOutcome>,
+   /// number of failed transfers of the payout to the current receiver, failed multisender batches aren't counted
+   pub attempts: u32,
+   pub last_error_timestamp: Option<WrappedDuration>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PayoutBatchOutcome {
+   Success,
+   Failure,
+}
+
+#[derive(Debug, Clone, BorshDeserialize, BorshSerialize, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PayoutRedraw {
+   pub account_id: AccountId,
+   pub timestamp: WrappedDuration,
+   /// why the prize was taken from the account
+   pub reason: String,
+}
+
+impl Payout {
+   /// New pending payout, which hasn't been sent yet
+   pub fn new(
+      account_id: AccountId,
+      amount: WrappedBalance,
+      token_id: Option<TokenId>,
+      nft: Option<NftReward>,
+      place: u64,
+      label: Option<String>,
+      refund: bool,
+   ) -> Self {
+      Self {
+         account_id,
+         amount,
+         token_id,
+         nft,
+         place,
+         label,
+         refund,
+         status: PayoutStatus::Pending,
+         redraws: vec![],
+         claim_deadline: None,
+         batch_id: None,
+         batch_outcome: None,
+         attempts: 0,
+         last_error_timestamp: None,
+      }
+   }
+
+   /// Winner of the draw before redraws, None for unawarded prizes
+   pub fn get_drawn_account_id(&self) -> Option<&AccountId> {
+      match self.redraws.first() {
+         Some(redraw) => Some(&redraw.account_id),
+         None if self.refund => None,
+         None => Some(&self.account_id),
+      }
+   }
 }
 
 impl From<VPayout> for Payout {
@@ -25,21 +102,321 @@ impl From<VPayout> for Payout {
    }
 }
 
+/// How distribute_payouts sends NEAR payouts
+#[derive(BorshSerialize, BorshDeserialize, Clone, Copy, Eq, PartialEq, Debug, Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PayoutBackend {
+   /// one batch through `multisender_contract`
+   Multisender,
+   /// a transfer with its own callback for every payout
+   Direct,
+}
+
 #[derive(BorshSerialize, BorshDeserialize, Clone, Eq, PartialEq, Debug, Serialize, Deserialize)]
 #[serde(crate = "near_sdk::serde")]
 pub enum PayoutStatus {
    Pending,
    Complete,
+   /// the last transfer failed, see `retry_failed_payouts`
+   Failed,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FailedPayoutOutput {
+   pub event_id: EventId,
+   pub payout_index: PayoutIndex,
+   pub payout: Payout,
+}
+
+/// Sends a NEAR payout of the batch and marks it `Failed` in the callback if the transfer failed
+pub(crate) fn near_transfer_payout(event_id: EventId, batch_id: PayoutBatchId, payout_index: PayoutIndex, payout: &Payout) -> Promise {
+   Promise::new(payout.account_id.to_owned())
+      .transfer(payout.amount.0)
+      .then(ext_self::after_payout_transfer(
+         event_id,
+         batch_id,
+         payout_index,
+         env::current_account_id(),
+         NO_DEPOSIT,
+         GAS_FOR_AFTER_PAYOUT_TRANSFER,
+      ))
+}
+
+/// Gives the prize to the next reserve winner, without reserve winners the prize returns to the event owner.
+/// Reserve winners of claim mode events get a new claim period
+fn redraw(event: &mut Event, payout: &mut Payout, reason: String) {
+   payout.redraws.push(PayoutRedraw {
+      account_id: payout.account_id.clone(),
+      timestamp: env::block_timestamp().into(),
+      reason,
+   });
+   match event.reserve_winners.get(event.used_reserve_winners) {
+      Some(reserve_account_id) => {
+         log!("Prize #{} of @{} goes to reserve winner @{}", payout.place, payout.account_id, reserve_account_id);
+         event.used_reserve_winners += 1;
+         payout.account_id = reserve_account_id;
+         payout.claim_deadline = event.claim_period
+            .map(|claim_period| (env::block_timestamp() + claim_period).into());
+      }
+      None => {
+         log!("No reserve winners left, prize #{} returns to @{}", payout.place, event.owner_account_id);
+         payout.account_id = event.owner_account_id.clone();
+         payout.refund = true;
+         payout.claim_deadline = None;
+      }
+   }
+   payout.status = PayoutStatus::Pending;
+   payout.attempts = 0;
+   payout.last_error_timestamp = None;
 }
 
 impl Giveaway {
    pub fn internal_get_payouts(&self, event_id: &u64) -> Vec<Payout> {
       self.payouts.get(event_id).unwrap_or_else(|| [].to_vec())
    }
+
+   pub(crate) fn internal_next_payout_batch_id(&mut self) -> PayoutBatchId {
+      let batch_id = self.next_payout_batch_id;
+      self.next_payout_batch_id += 1;
+      batch_id
+   }
+
+   /// Records the outcome on payouts of the batch, payouts sent by later batches are left intact.
+   /// With `own_transfer` every payout had its own transfer, so a failure counts as an attempt
+   pub(crate) fn internal_record_batch_outcome(
+      &mut self,
+      event_id: u64,
+      batch_id: PayoutBatchId,
+      payout_indices: &[PayoutIndex],
+      success: bool,
+      own_transfer: bool,
+   ) {
+      let mut payouts = self.internal_get_payouts(&event_id);
+      for payout_index in payout_indices {
+         if let Some(payout) = payouts.get_mut(*payout_index as usize) {
+            if payout.batch_id != Some(batch_id) {
+               continue;
+            }
+            if success {
+               payout.batch_outcome = Some(PayoutBatchOutcome::Success);
+            } else {
+               log!("Payout {} for @{} failed in batch {}", payout_index, payout.account_id, batch_id);
+               payout.batch_outcome = Some(PayoutBatchOutcome::Failure);
+               payout.status = PayoutStatus::Failed;
+               payout.last_error_timestamp = Some(env::block_timestamp().into());
+               if own_transfer {
+                  payout.attempts += 1;
+               }
+            }
+         }
+      }
+      self.payouts.insert(&event_id, &payouts);
+   }
 }
 
 #[near_bindgen]
 impl Giveaway {
+   #[private]
+   pub fn after_payout_transfer(&mut self, event_id: u64, batch_id: PayoutBatchId, payout_index: PayoutIndex) -> bool {
+      let promise_success = is_promise_success();
+      self.internal_record_batch_outcome(event_id, batch_id, &[payout_index], promise_success, true);
+
+      promise_success
+   }
+
+   /// Gives the prize of a payout failed `MAX_PAYOUT_ATTEMPTS` times or unclaimed before its claim deadline to the next reserve winner,
+   /// without reserve winners the prize returns to the event owner
+   pub fn redraw_payout(&mut self, event_id: u64, payout_index: PayoutIndex) {
+      self.assert_active();
+      let mut event = self.internal_get_event(&event_id);
+      self.assert_event_owner(&event);
+      assert_eq!(event.status, EventStatus::Calculated, "Method is not available");
+
+      let mut payouts = self.internal_get_payouts(&event_id);
+      let payout = payouts.get_mut(payout_index as usize).expect("ERR_NO_PAYOUT");
+      assert!(
+         matches!(payout.status, PayoutStatus::Pending | PayoutStatus::Failed),
+         "Payout is already sent"
+      );
+      assert!(!payout.refund, "Refunds can't be redrawn");
+      let attempts_exhausted = payout.status == PayoutStatus::Failed && payout.attempts >= MAX_PAYOUT_ATTEMPTS;
+      let claim_expired = payout.claim_deadline
+         .map(|claim_deadline| env::block_timestamp() >= claim_deadline.0)
+         .unwrap_or(false);
+      assert!(attempts_exhausted || claim_expired, "Only payouts failed {} times or unclaimed in time can be redrawn", MAX_PAYOUT_ATTEMPTS);
+
+      let reason = if attempts_exhausted {
+         format!("Payout failed {} times", payout.attempts)
+      } else {
+         "Prize wasn't claimed in time".to_string()
+      };
+      redraw(&mut event, payout, reason);
+
+      self.events.insert(&event_id, &event);
+      self.payouts.insert(&event_id, &payouts);
+   }
+
+   /// Takes the unsent prize from a winner who broke the event rules and gives it to the next reserve winner,
+   /// without reserve winners the prize returns to the event owner. The reason is kept in `redraws` of the payout
+   pub fn disqualify_winner(&mut self, event_id: u64, payout_index: PayoutIndex, reason: String) {
+      self.assert_active();
+      let mut event = self.internal_get_event(&event_id);
+      self.assert_event_owner(&event);
+      assert_eq!(event.status, EventStatus::Calculated, "Method is not available");
+      assert!(!reason.is_empty(), "Reason is required");
+      assert!(reason.len() < MAX_REDRAW_REASON_LENGTH, "Reason is too long");
+
+      let mut payouts = self.internal_get_payouts(&event_id);
+      let payout = payouts.get_mut(payout_index as usize).expect("ERR_NO_PAYOUT");
+      assert!(
+         matches!(payout.status, PayoutStatus::Pending | PayoutStatus::Failed),
+         "Payout is already sent"
+      );
+      assert!(!payout.refund, "Refunds can't be redrawn");
+
+      log!("@{} is disqualified: {}", payout.account_id, reason);
+      redraw(&mut event, payout, reason);
+
+      self.events.insert(&event_id, &event);
+      self.payouts.insert(&event_id, &payouts);
+   }
+
+   /// Sends pending prizes of the caller in a claim mode event
+   pub fn claim(&mut self, event_id: u64) -> Promise {
+      self.assert_active();
+      let event = self.internal_get_event(&event_id);
+      assert_eq!(event.status, EventStatus::Calculated, "Method is not available");
+
+      let account_id = env::predecessor_account_id();
+      let current_timestamp = env::block_timestamp();
+      let batch_id = self.internal_next_payout_batch_id();
+      let mut payouts = self.internal_get_payouts(&event_id);
+      let mut promises: Vec<Promise> = vec![];
+      for (payout_index, payout) in payouts.iter_mut().enumerate() {
+         let claimable = payout.account_id == account_id
+            && matches!(payout.status, PayoutStatus::Pending | PayoutStatus::Failed)
+            && payout.claim_deadline.map(|claim_deadline| current_timestamp < claim_deadline.0).unwrap_or(false);
+         if !claimable {
+            continue;
+         }
+
+         let payout_index = payout_index as PayoutIndex;
+         promises.push(if let Some(nft_reward) = &payout.nft {
+            nft_transfer_payout(event_id, batch_id, payout_index, nft_reward, payout)
+         } else if let Some(token_id) = &payout.token_id {
+            ft_transfer_payout(event_id, batch_id, payout_index, token_id, payout)
+         } else {
+            near_transfer_payout(event_id, batch_id, payout_index, payout)
+         });
+         payout.status = PayoutStatus::Complete;
+         payout.batch_id = Some(batch_id);
+         payout.batch_outcome = None;
+         log!("@{} claimed prize #{}", account_id, payout.place);
+      }
+      self.payouts.insert(&event_id, &payouts);
+
+      promises.into_iter()
+         .reduce(|promises, promise| promises.and(promise))
+         .expect("Nothing to claim")
+   }
+
+   /// Gives prizes unclaimed before their deadline to reserve winners or back to the event owner.
+   /// Returns the number of expired prizes
+   pub fn expire_unclaimed_payouts(&mut self, event_id: u64) -> u64 {
+      self.assert_active();
+      let mut event = self.internal_get_event(&event_id);
+      assert_eq!(event.status, EventStatus::Calculated, "Method is not available");
+
+      let current_timestamp = env::block_timestamp();
+      let mut payouts = self.internal_get_payouts(&event_id);
+      let mut expired: u64 = 0;
+      for payout in payouts.iter_mut() {
+         let unclaimed = matches!(payout.status, PayoutStatus::Pending | PayoutStatus::Failed)
+            && payout.claim_deadline.map(|claim_deadline| current_timestamp >= claim_deadline.0).unwrap_or(false);
+         if unclaimed {
+            redraw(&mut event, payout, "Prize wasn't claimed in time".to_string());
+            expired += 1;
+         }
+      }
+
+      self.events.insert(&event_id, &event);
+      self.payouts.insert(&event_id, &payouts);
+      expired
+   }
+
+   /// Sends failed payouts of the event again together with other pending ones. Receivers retry their own payouts at any time.
+   /// The event owner retries payouts of failed multisender batches, but payouts failed by their own transfer only
+   /// `PAYOUT_RETRY_DELAY` after the failure, so the receiver has time to fix it before the attempts are spent.
+   /// Payouts failed `MAX_PAYOUT_ATTEMPTS` times can be redrawn by the event owner, refunds can't be redrawn and are retried without limit.
+   /// Returns `from_index` of the next distribute_payouts call, None when all payouts are sent
+   pub fn retry_failed_payouts(&mut self, event_id: u64) -> Option<U64> {
+      self.assert_active();
+      let event = self.internal_get_event(&event_id);
+      let account_id = env::predecessor_account_id();
+      let is_event_owner = account_id == event.owner_account_id;
+      let current_timestamp = env::block_timestamp();
+
+      let mut payouts = self.internal_get_payouts(&event_id);
+      let mut retried: u64 = 0;
+      for payout in payouts.iter_mut() {
+         // Claim mode winners retry with `claim`
+         let attempts_exhausted = payout.attempts >= MAX_PAYOUT_ATTEMPTS && !payout.refund;
+         if payout.status != PayoutStatus::Failed || payout.claim_deadline.is_some() || attempts_exhausted {
+            continue;
+         }
+         let retry_allowed = payout.account_id == account_id || (is_event_owner && (
+            payout.attempts == 0 || payout.last_error_timestamp
+               .map(|last_error_timestamp| current_timestamp >= last_error_timestamp.0 + PAYOUT_RETRY_DELAY)
+               .unwrap_or(true)
+         ));
+         if retry_allowed {
+            payout.status = PayoutStatus::Pending;
+            retried += 1;
+         }
+      }
+      assert!(retried > 0, "No failed payouts to retry");
+      self.payouts.insert(&event_id, &payouts);
+      log!("Retrying {} failed payouts", retried);
+
+      self.distribute_payouts(event_id, None, None)
+   }
+
+   pub fn set_payout_backend(&mut self, payout_backend: PayoutBackend) {
+      self.assert_contract_owner();
+      self.payout_backend = payout_backend;
+   }
+
+   /// Event owner can switch NEAR payouts of the event, None uses the contract backend
+   pub fn set_event_payout_backend(&mut self, event_id: u64, payout_backend: Option<PayoutBackend>) {
+      self.assert_active();
+      let mut event = self.internal_get_event(&event_id);
+      self.assert_event_owner(&event);
+      assert_ne!(event.status, EventStatus::Distributed, "Event is already distributed");
+
+      event.payout_backend = payout_backend;
+      self.events.insert(&event_id, &event);
+   }
+
+   pub fn get_payout_backend(&self) -> PayoutBackend {
+      self.payout_backend
+   }
+
+   /// Failed payouts of events from `from_index` to `from_index + limit`
+   pub fn get_failed_payouts(&self, from_index: u64, limit: u64) -> Vec<FailedPayoutOutput> {
+      (from_index..std::cmp::min(from_index + limit, self.events.len()))
+         .flat_map(|event_id| {
+            self.internal_get_payouts(&event_id).into_iter().enumerate()
+               .filter(|(_, payout)| payout.status == PayoutStatus::Failed)
+               .map(move |(payout_index, payout)| FailedPayoutOutput {
+                  event_id,
+                  payout_index: payout_index as PayoutIndex,
+                  payout,
+               })
+         })
+         .collect()
+   }
+
    pub fn get_payouts(&self, event_id: u64, from_index: Option<u64>, limit: Option<u64>) -> Vec<Payout> {
       let payouts = self.internal_get_payouts(&event_id);
       if from_index.is_none() && limit.is_none(){
@@ -53,3 +430,210 @@ impl Giveaway {
          .collect()
    }
 }
+
+#[cfg(test)]
+mod tests {
+   use near_sdk::test_utils::accounts;
+   use near_sdk::{testing_env, PromiseResult};
+
+   use crate::tests::*;
+
+   use super::*;
+
+   const CLAIM_PERIOD: Duration = 100;
+
+   /// Finalized claim mode event with `rewards_number` prizes
+   fn setup_claim_event(rewards_number: u64, tickets: &[TicketsNumber], reserve_winners_number: u64) -> (Giveaway, EventId) {
+      let mut event_input = get_event_input(rewards_number, tickets, reserve_winners_number);
+      event_input.claim_period = Some(U64(CLAIM_PERIOD));
+      let (mut contract, event_id) = add_event(event_input);
+      set_context(accounts(0), 0);
+      assert!(contract.finalize_event(event_id, None));
+      (contract, event_id)
+   }
+
+   #[test]
+   fn test_claim() {
+      let (mut contract, event_id) = setup_claim_event(2, &[1, 1], 0);
+      let payouts = contract.internal_get_payouts(&event_id);
+      assert!(payouts.iter().all(|payout| payout.claim_deadline == Some(U64(CLAIM_PERIOD))));
+      // Prizes are pulled by winners
+      assert_eq!(contract.distribute_payouts(event_id, None, None), None);
+      assert!(contract.internal_get_payouts(&event_id).iter().all(|payout| payout.status == PayoutStatus::Pending));
+
+      let winner_id = payouts[0].account_id.clone();
+      testing_env!(get_context(winner_id.clone(), CLAIM_PERIOD - 1).build());
+      contract.claim(event_id);
+      let payout = &contract.internal_get_payouts(&event_id)[0];
+      assert_eq!(payout.status, PayoutStatus::Complete);
+      let batch_id = payout.batch_id.expect("ERR_NO_BATCH");
+
+      // The winner claims again after the failed transfer
+      set_promise_result(PromiseResult::Failed);
+      assert!(!contract.after_payout_transfer(event_id, batch_id, 0));
+      assert_eq!(contract.internal_get_payouts(&event_id)[0].status, PayoutStatus::Failed);
+
+      testing_env!(get_context(winner_id, CLAIM_PERIOD - 1).build());
+      contract.claim(event_id);
+      let payout = &contract.internal_get_payouts(&event_id)[0];
+      assert_eq!(payout.status, PayoutStatus::Complete);
+      assert_ne!(payout.batch_id, Some(batch_id));
+      assert_eq!(contract.internal_get_payouts(&event_id)[1].status, PayoutStatus::Pending);
+   }
+
+   #[test]
+   #[should_panic(expected = "Nothing to claim")]
+   fn test_claim_after_deadline() {
+      let (mut contract, event_id) = setup_claim_event(1, &[1], 0);
+      let winner_id = contract.internal_get_payouts(&event_id)[0].account_id.clone();
+      testing_env!(get_context(winner_id, CLAIM_PERIOD).build());
+      contract.claim(event_id);
+   }
+
+   #[test]
+   fn test_expire_unclaimed_payouts() {
+      let (mut contract, event_id) = setup_claim_event(2, &[1, 1, 1], 1);
+      let payouts = contract.internal_get_payouts(&event_id);
+      let reserve_winner_id = contract.internal_get_event(&event_id).reserve_winners.get(0).expect("ERR_NO_RESERVE_WINNER");
+
+      testing_env!(get_context(payouts[0].account_id.clone(), CLAIM_PERIOD - 1).build());
+      contract.claim(event_id);
+      assert_eq!(contract.expire_unclaimed_payouts(event_id), 0);
+
+      // The unclaimed prize goes to the reserve winner with a new claim period
+      testing_env!(get_context(accounts(0), CLAIM_PERIOD).build());
+      assert_eq!(contract.expire_unclaimed_payouts(event_id), 1);
+      let payout = &contract.internal_get_payouts(&event_id)[1];
+      assert_eq!(payout.account_id, reserve_winner_id);
+      assert_eq!(payout.claim_deadline, Some(U64(2 * CLAIM_PERIOD)));
+      assert_eq!(payout.get_drawn_account_id(), Some(&payouts[1].account_id));
+      assert_eq!(payout.redraws[0].reason, "Prize wasn't claimed in time");
+      assert_eq!(contract.internal_get_payouts(&event_id)[0].status, PayoutStatus::Complete);
+
+      // Without reserve winners the prize returns to the event owner
+      testing_env!(get_context(accounts(0), 2 * CLAIM_PERIOD).build());
+      assert_eq!(contract.expire_unclaimed_payouts(event_id), 1);
+      let payout = &contract.internal_get_payouts(&event_id)[1];
+      assert_eq!(payout.account_id, accounts(1));
+      assert!(payout.refund);
+      assert_eq!(payout.claim_deadline, None);
+      assert_eq!(payout.status, PayoutStatus::Pending);
+      assert_eq!(payout.redraws.len(), 2);
+
+      // The refund is sent with distribute_payouts
+      assert_eq!(contract.distribute_payouts(event_id, None, None), None);
+      assert_eq!(contract.internal_get_payouts(&event_id)[1].status, PayoutStatus::Complete);
+   }
+
+   #[test]
+   fn test_batch_outcome_reconciliation() {
+      let (mut contract, event_id) = setup(2, &[1, 1], 0);
+      set_context(accounts(0), 0);
+      assert!(contract.finalize_event(event_id, None));
+      assert_eq!(contract.distribute_payouts(event_id, None, None), None);
+      let payouts = contract.internal_get_payouts(&event_id);
+      let batch_id = payouts[0].batch_id.expect("ERR_NO_BATCH");
+      assert!(payouts.iter().all(|payout| payout.batch_id == Some(batch_id) && payout.batch_outcome.is_none()));
+
+      // The failed multisender batch fails all its payouts without spending their attempts
+      set_promise_result(PromiseResult::Failed);
+      assert!(!contract.after_multisend_attached_tokens(event_id, batch_id, vec![0, 1]));
+      for payout in contract.internal_get_payouts(&event_id) {
+         assert_eq!(payout.status, PayoutStatus::Failed);
+         assert_eq!(payout.batch_outcome, Some(PayoutBatchOutcome::Failure));
+         assert_eq!(payout.attempts, 0);
+      }
+
+      // Payouts of the failed batch are retried with their own transfers
+      set_context(accounts(1), 0);
+      assert_eq!(contract.retry_failed_payouts(event_id), None);
+      let payouts = contract.internal_get_payouts(&event_id);
+      let retry_batch_id = payouts[0].batch_id.expect("ERR_NO_BATCH");
+      assert_ne!(retry_batch_id, batch_id);
+      assert!(payouts.iter().all(|payout| payout.status == PayoutStatus::Complete && payout.batch_id == Some(retry_batch_id)));
+
+      // A late callback of the previous batch doesn't change payouts sent again
+      set_promise_result(PromiseResult::Failed);
+      contract.after_multisend_attached_tokens(event_id, batch_id, vec![0, 1]);
+      assert!(contract.internal_get_payouts(&event_id).iter().all(|payout| payout.status == PayoutStatus::Complete && payout.batch_outcome.is_none()));
+
+      set_promise_result(PromiseResult::Successful(vec![]));
+      assert!(contract.after_payout_transfer(event_id, retry_batch_id, 0));
+      set_promise_result(PromiseResult::Failed);
+      assert!(!contract.after_payout_transfer(event_id, retry_batch_id, 1));
+      let payouts = contract.internal_get_payouts(&event_id);
+      assert_eq!(payouts[0].status, PayoutStatus::Complete);
+      assert_eq!(payouts[0].batch_outcome, Some(PayoutBatchOutcome::Success));
+      assert_eq!(payouts[1].status, PayoutStatus::Failed);
+      assert_eq!(payouts[1].batch_outcome, Some(PayoutBatchOutcome::Failure));
+      assert_eq!(payouts[1].attempts, 1);
+   }
+
+   /// Fails the last transfer of the payout in its callback
+   fn fail_payout_transfer(contract: &mut Giveaway, event_id: EventId, payout_index: PayoutIndex) {
+      let batch_id = contract.internal_get_payouts(&event_id)[payout_index as usize].batch_id.expect("ERR_NO_BATCH");
+      set_promise_result(PromiseResult::Failed);
+      contract.after_payout_transfer(event_id, batch_id, payout_index);
+   }
+
+   fn setup_failed_payout() -> (Giveaway, EventId) {
+      let mut event_input = get_event_input(1, &[1, 1], 1);
+      event_input.payout_backend = Some(PayoutBackend::Direct);
+      let (mut contract, event_id) = add_event(event_input);
+      set_context(accounts(0), 0);
+      assert!(contract.finalize_event(event_id, None));
+      contract.distribute_payouts(event_id, None, None);
+      fail_payout_transfer(&mut contract, event_id, 0);
+      (contract, event_id)
+   }
+
+   #[test]
+   fn test_retry_and_redraw_failed_payout() {
+      let (mut contract, event_id) = setup_failed_payout();
+      let winner_id = contract.internal_get_payouts(&event_id)[0].account_id.clone();
+      let reserve_winner_id = contract.internal_get_event(&event_id).reserve_winners.get(0).expect("ERR_NO_RESERVE_WINNER");
+      assert_eq!(contract.internal_get_payouts(&event_id)[0].attempts, 1);
+
+      // The receiver retries at any time
+      set_context(winner_id.clone(), 0);
+      contract.retry_failed_payouts(event_id);
+      assert_eq!(contract.internal_get_payouts(&event_id)[0].status, PayoutStatus::Complete);
+      fail_payout_transfer(&mut contract, event_id, 0);
+
+      // The event owner retries after the delay
+      testing_env!(get_context(accounts(1), PAYOUT_RETRY_DELAY).build());
+      contract.retry_failed_payouts(event_id);
+      fail_payout_transfer(&mut contract, event_id, 0);
+      let payout = &contract.internal_get_payouts(&event_id)[0];
+      assert_eq!(payout.status, PayoutStatus::Failed);
+      assert_eq!(payout.attempts, MAX_PAYOUT_ATTEMPTS);
+
+      set_context(accounts(1), 0);
+      contract.redraw_payout(event_id, 0);
+      let payout = &contract.internal_get_payouts(&event_id)[0];
+      assert_eq!(payout.account_id, reserve_winner_id);
+      assert_eq!(payout.status, PayoutStatus::Pending);
+      assert_eq!(payout.attempts, 0);
+      assert_eq!(payout.get_drawn_account_id(), Some(&winner_id));
+      assert_eq!(payout.redraws[0].reason, format!("Payout failed {} times", MAX_PAYOUT_ATTEMPTS));
+
+      contract.distribute_payouts(event_id, None, None);
+      assert_eq!(contract.internal_get_payouts(&event_id)[0].status, PayoutStatus::Complete);
+   }
+
+   #[test]
+   #[should_panic(expected = "No failed payouts to retry")]
+   fn test_owner_retry_before_delay() {
+      let (mut contract, event_id) = setup_failed_payout();
+      testing_env!(get_context(accounts(1), PAYOUT_RETRY_DELAY - 1).build());
+      contract.retry_failed_payouts(event_id);
+   }
+
+   #[test]
+   #[should_panic(expected = "Only payouts failed 3 times or unclaimed in time can be redrawn")]
+   fn test_redraw_before_attempts_exhausted() {
+      let (mut contract, event_id) = setup_failed_payout();
+      set_context(accounts(1), 0);
+      contract.redraw_payout(event_id, 0);
+   }
+}
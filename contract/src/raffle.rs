@@ -0,0 +1,134 @@
+use crate::*;
+use crate::utils::is_promise_success;
+
+/// Splits the prize pool by tiers in basis points, rounding leftovers go to the first tier
+pub fn split_prize_pool(prize_pool: Balance, pool_tiers: &[u32]) -> Vec<Balance> {
+   let mut prizes: Vec<Balance> = pool_tiers.iter()
+      .map(|tier| prize_pool * *tier as Balance / BASIS_POINTS as Balance)
+      .collect();
+   let distributed: Balance = prizes.iter().sum();
+   if let Some(first_prize) = prizes.first_mut() {
+      *first_prize += prize_pool - distributed;
+   }
+   prizes
+}
+
+pub fn assert_valid_pool_tiers(pool_tiers: &[u32]) {
+   assert!(pool_tiers.iter().all(|tier| *tier > 0), "Pool tiers should be positive");
+   assert_eq!(pool_tiers.iter().sum::<u32>(), BASIS_POINTS, "Pool tiers should sum up to 10000 basis points");
+}
+
+impl Giveaway {
+   /// Adds tickets bought for `amount` of `token_id`. Returns the change and the cost of the storage used by the buyer,
+   /// which the caller charges in NEAR
+   pub(crate) fn internal_buy_tickets(&mut self, event_id: EventId, account_id: &AccountId, token_id: &Option<TokenId>, amount: Balance) -> (Balance, Balance) {
+      self.assert_active();
+      let mut event: Event = self.internal_get_event(&event_id);
+
+      let ticket_price = event.ticket_price.expect("Tickets are not for sale");
+      assert!(event.open_registration, "Registration is closed");
+      assert_eq!(&event.rewards_token_id, token_id, "ERR_WRONG_TOKEN");
+      assert_participants_update_allowed(&event);
+
+      let tickets = amount / ticket_price;
+      assert!(tickets > 0, "Not enough deposit to buy a ticket");
+      if !event.allow_duplicate_participants {
+         if let Some(index) = event.get_participant_index(account_id) {
+            assert_eq!(event.tickets.get(index), 0, "Already joined");
+         }
+      }
+      let payment = tickets * ticket_price;
+
+      let initial_storage_usage = env::storage_usage();
+      event.add_participant(account_id, tickets as TicketsNumber);
+      event.prize_pool += payment;
+      self.events.insert(&event_id, &event);
+
+      log!("@{} bought {} tickets of event {}", account_id, tickets, event_id);
+
+      (amount - payment, get_storage_cost(initial_storage_usage))
+   }
+
+   /// Takes the service fee from the prize pool and returns the rest to be split among winners
+   pub(crate) fn internal_take_prize_pool_fee(&mut self, event: &Event) -> Balance {
+      if event.prize_pool == 0 {
+         return 0;
+      }
+      let service_fee = self.get_service_fee(&event.rewards_token_id, &event.owner_account_id, &event.prize_pool);
+      self.internal_add_service_fee(&event.rewards_token_id, &service_fee);
+      event.prize_pool - service_fee
+   }
+}
+
+#[near_bindgen]
+impl Giveaway {
+   /// Buys tickets of the paid raffle with NEAR. The storage of a new buyer is paid from the change, the rest is returned
+   #[payable]
+   pub fn buy_tickets(&mut self, event_id: u64) -> WrappedTicketsNumber {
+      let account_id = env::predecessor_account_id();
+      let (change, storage_cost) = self.internal_buy_tickets(event_id, &account_id, &None, env::attached_deposit());
+      assert!(change >= storage_cost, "Not enough deposit to pay for storage (Change: {}, Required: {})", change, storage_cost);
+      let tokens_to_return = change - storage_cost;
+
+      if tokens_to_return > 0 {
+         Promise::new(account_id.clone()).transfer(tokens_to_return);
+      }
+
+      let event = self.internal_get_event(&event_id);
+      event.tickets.get(event.get_participant_index(&account_id).unwrap()).into()
+   }
+
+   /// Returns the payment for tickets of a cancelled raffle to the caller. The service fee is taken from the prize pool
+   /// on finalization, so buyers get back all they paid
+   pub fn refund_tickets(&mut self, event_id: u64) -> Promise {
+      self.assert_active();
+      let mut event = self.internal_get_event(&event_id);
+      assert_eq!(event.status, EventStatus::Cancelled, "Event is not cancelled");
+      let ticket_price = event.ticket_price.expect("Tickets are not for sale");
+
+      let account_id = env::predecessor_account_id();
+      let index = event.get_participant_index(&account_id).expect("ERR_NOT_PARTICIPANT");
+      let tickets = event.tickets.get(index);
+      assert!(tickets > 0, "No tickets to refund");
+      let amount = tickets as Balance * ticket_price;
+      event.tickets.set(index, 0);
+      event.prize_pool -= amount;
+      self.events.insert(&event_id, &event);
+      log!("@{} gets {} back for {} tickets", account_id, amount, tickets);
+
+      let transfer = match &event.rewards_token_id {
+         Some(token_id) => ext_ft::ft_transfer(
+            account_id.clone(),
+            amount.into(),
+            Some(format!("Giveaway #{} ticket refund", event_id)),
+            token_id.to_owned(),
+            ONE_YOCTO,
+            GAS_FOR_FT_TRANSFER),
+         None => Promise::new(account_id.clone()).transfer(amount),
+      };
+      transfer.then(ext_self::after_refund_tickets(
+         event_id,
+         account_id,
+         tickets.into(),
+         env::current_account_id(),
+         NO_DEPOSIT,
+         GAS_FOR_AFTER_PAYOUT_TRANSFER,
+      ))
+   }
+
+   /// Gives the tickets back if the refund failed, so it can be requested again
+   #[private]
+   pub fn after_refund_tickets(&mut self, event_id: u64, account_id: AccountId, tickets: WrappedTicketsNumber) -> bool {
+      let promise_success = is_promise_success();
+      if !promise_success {
+         let mut event = self.internal_get_event(&event_id);
+         log!("Ticket refund of @{} failed", account_id);
+         let index = event.get_participant_index(&account_id).unwrap();
+         event.tickets.set(index, tickets.0);
+         event.prize_pool += tickets.0 as Balance * event.ticket_price.unwrap();
+         self.events.insert(&event_id, &event);
+      }
+
+      promise_success
+   }
+}
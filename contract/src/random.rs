@@ -0,0 +1,213 @@
+//! Winner selection randomness.
+//!
+//! Draws are reproducible from the `env::random_seed()` of the first finalize_event call:
+//!
+//! 1. `hash = sha256(seed || event_id || counter)`, where `event_id` and `counter` are little-endian u64
+//!    and `counter` starts at 0 and is incremented after every hash.
+//! 2. `value` is the first 8 bytes of `hash` as a little-endian u64.
+//! 3. To pick an index among `n` entries, values `>= u64::MAX - u64::MAX % n` are rejected and the next
+//!    counter is hashed, otherwise the index is `value % n`. This keeps every index equally likely.
+//!
+//! Winners are drawn prize by prize in the event order. Every draw picks a ticket index among `remaining`
+//! tickets, counting tickets in participants order, and the holder of that ticket wins. All tickets of the winner
+//! then leave the draw, so accounts win only once. When no tickets remain, the rest of the prizes go to the event owner.
+//! Reserve winners are drawn the same way after all prizes, until no tickets remain.
+//!
+//! The seed and the algorithm version are stored in `Event.draw`, `verify_draw` replays the draw on-chain.
+//! The replay reads tickets of every participant, so it's limited to `MAX_VERIFY_DRAW_PARTICIPANTS`,
+//! draws of larger events are verified off-chain only, with the `giveaway-verify` tool in `/verifier`.
+
+use crate::*;
+
+pub struct DrawRng {
+   seed: Vec<u8>,
+   event_id: EventId,
+   counter: u64,
+}
+
+impl DrawRng {
+   /// `counter` is the number of hashes used before, to continue the draw
+   pub fn new(seed: Vec<u8>, event_id: EventId, counter: u64) -> Self {
+      Self {
+         seed,
+         event_id,
+         counter,
+      }
+   }
+
+   pub fn get_counter(&self) -> u64 {
+      self.counter
+   }
+
+   fn next_u64(&mut self) -> u64 {
+      let mut input = self.seed.clone();
+      input.extend_from_slice(&self.event_id.to_le_bytes());
+      input.extend_from_slice(&self.counter.to_le_bytes());
+      self.counter += 1;
+
+      let hash = env::sha256(&input);
+      let mut value = [0u8; 8];
+      value.copy_from_slice(&hash[..8]);
+      u64::from_le_bytes(value)
+   }
+
+   /// Uniformly distributed index in `0..n`
+   pub fn next_index(&mut self, n: u64) -> u64 {
+      assert!(n > 0, "ERR_EMPTY_RANGE");
+      let zone = u64::MAX - u64::MAX % n;
+      loop {
+         let value = self.next_u64();
+         if value < zone {
+            return value % n;
+         }
+      }
+   }
+}
+
+/// Tickets in the draw kept in memory to replay a finished draw in a view call
+struct DrawTickets {
+   /// Fenwick tree of tickets, the same layout as in `Tickets`
+   tree: Vec<TicketsNumber>,
+   remaining: TicketsNumber,
+}
+
+impl DrawTickets {
+   fn new(counts: &[TicketsNumber]) -> Self {
+      let mut tree = counts.to_vec();
+      for position in 1..=tree.len() {
+         let parent = position + lowbit(position);
+         if parent <= tree.len() {
+            tree[parent - 1] += tree[position - 1];
+         }
+      }
+      Self {
+         tree,
+         remaining: counts.iter().sum(),
+      }
+   }
+
+   fn find(&self, ticket: TicketsNumber) -> usize {
+      let mut position = 0;
+      let mut rest = ticket;
+      let mut step = if self.tree.is_empty() { 0 } else { 1usize << (usize::BITS - 1 - self.tree.len().leading_zeros()) };
+      while step > 0 {
+         let next = position + step;
+         if next <= self.tree.len() && self.tree[next - 1] <= rest {
+            position = next;
+            rest -= self.tree[next - 1];
+         }
+         step >>= 1;
+      }
+      position
+   }
+
+   fn remove(&mut self, index: usize, count: TicketsNumber) {
+      let mut position = index + 1;
+      while position <= self.tree.len() {
+         self.tree[position - 1] -= count;
+         position += lowbit(position);
+      }
+      self.remaining -= count;
+   }
+}
+
+fn lowbit(position: usize) -> usize {
+   position & position.wrapping_neg()
+}
+
+/// Indexes of the first `winners_number` winners of prizes and reserve winners, None when no tickets remain
+pub fn replay_draw(random_seed: Vec<u8>, event_id: EventId, counts: &[TicketsNumber], winners_number: u64) -> Vec<Option<u64>> {
+   let mut rng = DrawRng::new(random_seed, event_id, 0);
+   let mut tickets = DrawTickets::new(counts);
+   (0..winners_number).map(|_| {
+      if tickets.remaining == 0 {
+         None
+      } else {
+         let winner_index = tickets.find(rng.next_index(tickets.remaining));
+         tickets.remove(winner_index, counts[winner_index]);
+         Some(winner_index as u64)
+      }
+   }).collect()
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct DrawVerification {
+   pub random_seed: Base64VecU8,
+   pub algorithm_version: u8,
+   /// drawn receivers of the assigned prizes according to the replayed draw
+   pub expected_winners: Vec<AccountId>,
+   pub expected_reserve_winners: Vec<AccountId>,
+   /// indexes of payouts which don't match the replayed draw, redrawn payouts are compared by their first winner
+   pub mismatched_payouts: Vec<u64>,
+   pub reserve_winners_verified: bool,
+   pub verified: bool,
+}
+
+#[near_bindgen]
+impl Giveaway {
+   /// Recomputes winners from the stored participants, tickets and seed and compares them with payouts.
+   /// Only for events up to `MAX_VERIFY_DRAW_PARTICIPANTS` participants, use giveaway-verify for larger ones
+   pub fn verify_draw(&self, event_id: EventId) -> DrawVerification {
+      let event = self.internal_get_event(&event_id);
+      let draw = event.draw.as_ref().expect("ERR_NO_DRAW");
+      assert_eq!(draw.algorithm_version, DRAW_ALGORITHM_VERSION, "ERR_UNKNOWN_DRAW_ALGORITHM");
+      assert!(
+         event.participants.len() <= MAX_VERIFY_DRAW_PARTICIPANTS,
+         "Too many participants to verify the draw on-chain, use giveaway-verify"
+      );
+
+      let counts: Vec<TicketsNumber> = (0..event.participants.len()).map(|index| event.tickets.get(index)).collect();
+      let mut winners = replay_draw(draw.random_seed.clone(), event_id, &counts, draw.cursor);
+      let reserves = winners.split_off(std::cmp::min(event.get_prizes(draw.prize_pool).len(), winners.len()));
+
+      let payouts = self.internal_get_payouts(&event_id);
+      let mismatched_payouts: Vec<u64> = (0..std::cmp::max(payouts.len(), winners.len()))
+         .filter(|&index| match (payouts.get(index), winners.get(index)) {
+            (Some(payout), Some(winner_index)) => {
+               payout.get_drawn_account_id() != winner_index.map(|winner_index| event.participants.get(winner_index).unwrap()).as_ref()
+            }
+            _ => true,
+         })
+         .map(|index| index as u64)
+         .collect();
+
+      let expected_winners: Vec<AccountId> = winners.into_iter()
+         .map(|winner_index| match winner_index {
+            Some(winner_index) => event.participants.get(winner_index).unwrap(),
+            None => event.owner_account_id.clone(),
+         })
+         .collect();
+      let expected_reserve_winners: Vec<AccountId> = reserves.into_iter()
+         .flatten()
+         .map(|reserve_index| event.participants.get(reserve_index).unwrap())
+         .collect();
+      let reserve_winners_verified = expected_reserve_winners == event.reserve_winners.to_vec();
+
+      DrawVerification {
+         random_seed: draw.random_seed.clone().into(),
+         algorithm_version: draw.algorithm_version,
+         expected_winners,
+         expected_reserve_winners,
+         verified: mismatched_payouts.is_empty() && reserve_winners_verified,
+         mismatched_payouts,
+         reserve_winners_verified,
+      }
+   }
+}
+
+#[cfg(test)]
+mod tests {
+   use near_sdk::test_utils::VMContextBuilder;
+   use near_sdk::testing_env;
+
+   use super::*;
+
+   #[test]
+   fn test_replay_draw() {
+      testing_env!(VMContextBuilder::new().build());
+      // The same draw is replayed with sha2 by the verifier, see verifier/tests/fixtures
+      let winners = replay_draw(vec![7; 32], 42, &[1, 0, 3, 2, 5, 1], 8);
+      assert_eq!(winners, vec![Some(2), Some(4), Some(3), Some(5), Some(0), None, None, None]);
+   }
+}
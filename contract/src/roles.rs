@@ -0,0 +1,83 @@
+use near_sdk::collections::UnorderedSet;
+
+use crate::*;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Copy, Eq, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum Role {
+   /// Can pause the contract
+   PauseGuardian,
+   /// Manages whitelisted tokens, fee policies and fee-exempt accounts
+   FeeManager,
+}
+
+impl Giveaway {
+   fn internal_get_role_holders(&self, role: Role) -> &UnorderedSet<AccountId> {
+      match role {
+         Role::PauseGuardian => &self.pause_guardians,
+         Role::FeeManager => &self.fee_managers,
+      }
+   }
+
+   fn internal_get_role_holders_mut(&mut self, role: Role) -> &mut UnorderedSet<AccountId> {
+      match role {
+         Role::PauseGuardian => &mut self.pause_guardians,
+         Role::FeeManager => &mut self.fee_managers,
+      }
+   }
+
+   /// Contract owner has all roles
+   pub fn has_role(&self, account_id: &AccountId, role: Role) -> bool {
+      account_id == &self.owner_id || self.internal_get_role_holders(role).contains(account_id)
+   }
+
+   pub fn assert_role(&self, role: Role) {
+      assert!(self.has_role(&env::predecessor_account_id(), role), "ERR_NO_ACCESS");
+   }
+}
+
+#[near_bindgen]
+impl Giveaway {
+   pub fn grant_role(&mut self, role: Role, account_id: AccountId) {
+      self.assert_contract_owner();
+
+      self.internal_get_role_holders_mut(role).insert(&account_id);
+   }
+
+   pub fn revoke_role(&mut self, role: Role, account_id: AccountId) {
+      self.assert_contract_owner();
+
+      self.internal_get_role_holders_mut(role).remove(&account_id);
+   }
+
+   /// First step of the ownership transfer, the new owner should call `accept_ownership`
+   pub fn propose_owner(&mut self, account_id: Option<AccountId>) {
+      self.assert_contract_owner();
+
+      self.proposed_owner_id = account_id;
+   }
+
+   pub fn accept_ownership(&mut self) {
+      let account_id = env::predecessor_account_id();
+      assert_eq!(Some(account_id.clone()), self.proposed_owner_id, "ERR_NO_ACCESS");
+
+      log!("Ownership transferred from @{} to @{}", self.owner_id, account_id);
+      self.owner_id = account_id;
+      self.proposed_owner_id = None;
+   }
+
+   pub fn get_owner(&self) -> AccountId {
+      self.owner_id.to_owned()
+   }
+
+   pub fn get_proposed_owner(&self) -> Option<AccountId> {
+      self.proposed_owner_id.to_owned()
+   }
+
+   pub fn get_role_holders(&self, role: Role, from_index: u64, limit: u64) -> Vec<AccountId> {
+      let accounts = self.internal_get_role_holders(role).as_vector();
+      (from_index..std::cmp::min(from_index + limit, accounts.len()))
+         .filter_map(|index| accounts.get(index))
+         .collect()
+   }
+}
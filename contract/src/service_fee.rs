@@ -0,0 +1,170 @@
+use crate::*;
+use crate::utils::is_promise_success;
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ServiceFeePolicy {
+   /// fee rate in basis points of the total rewards
+   pub fee_bps: u32,
+   /// fee cap in the token units, no cap for None
+   pub max_fee: Option<WrappedBalance>,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Eq, PartialEq, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub enum WithdrawalStatus {
+   Pending,
+   Complete,
+   Failed,
+}
+
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct ServiceFeeWithdrawal {
+   /// NEAR for None
+   pub token_id: Option<TokenId>,
+   pub amount: WrappedBalance,
+   pub receiver_id: AccountId,
+   pub timestamp: WrappedDuration,
+   pub status: WithdrawalStatus,
+}
+
+impl Giveaway {
+   pub fn internal_get_service_fee_policy(&self, token_id: &Option<TokenId>) -> ServiceFeePolicy {
+      self.token_service_fees.get(token_id).unwrap_or_else(|| self.default_service_fee.clone())
+   }
+
+   /// Service fee of the event with `total` rewards created by `account_id`
+   pub fn get_service_fee(&self, token_id: &Option<TokenId>, account_id: &AccountId, total: &Balance) -> Balance {
+      if self.fee_exempt_accounts.contains(account_id) {
+         return 0;
+      }
+
+      let policy = self.internal_get_service_fee_policy(token_id);
+      let fee = total * policy.fee_bps as Balance / BASIS_POINTS as Balance;
+      match policy.max_fee {
+         Some(max_fee) => std::cmp::min(max_fee.0, fee),
+         None => fee,
+      }
+   }
+}
+
+fn assert_valid_service_fee_policy(policy: &ServiceFeePolicy) {
+   assert!(policy.fee_bps <= BASIS_POINTS, "ERR_FEE_TOO_HIGH");
+}
+
+#[near_bindgen]
+impl Giveaway {
+   /// Sets the fee policy of the token, None `policy` removes it so the default one is used
+   pub fn set_token_service_fee(&mut self, token_id: Option<TokenId>, policy: Option<ServiceFeePolicy>) {
+      self.assert_role(Role::FeeManager);
+
+      if let Some(policy) = policy {
+         assert_valid_service_fee_policy(&policy);
+         self.token_service_fees.insert(&token_id, &policy);
+      } else {
+         self.token_service_fees.remove(&token_id);
+      }
+   }
+
+   pub fn set_default_service_fee(&mut self, policy: ServiceFeePolicy) {
+      self.assert_role(Role::FeeManager);
+      assert_valid_service_fee_policy(&policy);
+
+      self.default_service_fee = policy;
+   }
+
+   pub fn add_fee_exempt_account(&mut self, account_id: AccountId) {
+      self.assert_role(Role::FeeManager);
+
+      self.fee_exempt_accounts.insert(&account_id);
+   }
+
+   pub fn remove_fee_exempt_account(&mut self, account_id: AccountId) {
+      self.assert_role(Role::FeeManager);
+
+      self.fee_exempt_accounts.remove(&account_id);
+   }
+
+   pub fn get_service_fee_policy(&self, token_id: Option<TokenId>) -> ServiceFeePolicy {
+      self.internal_get_service_fee_policy(&token_id)
+   }
+
+   /// Service fee rate of the token in basis points
+   pub fn get_service_fee_bps(&self, token_id: Option<TokenId>) -> u32 {
+      self.internal_get_service_fee_policy(&token_id).fee_bps
+   }
+
+   pub fn is_fee_exempt_account(&self, account_id: AccountId) -> bool {
+      self.fee_exempt_accounts.contains(&account_id)
+   }
+
+   pub fn get_fee_exempt_accounts(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+      let accounts = self.fee_exempt_accounts.as_vector();
+      (from_index..std::cmp::min(from_index + limit, accounts.len()))
+         .filter_map(|index| accounts.get(index))
+         .collect()
+   }
+
+   /// Sends collected service fee to the `receiver_id`. The balance is restored if the transfer fails
+   pub fn withdraw_service_fee(&mut self, token_id: Option<TokenId>, amount: WrappedBalance, receiver_id: AccountId) -> Promise {
+      self.assert_contract_owner();
+
+      let balance = self.total_service_fee.get(&token_id).unwrap_or_default();
+      assert!(amount.0 > 0, "ERR_ZERO_AMOUNT");
+      assert!(amount.0 <= balance, "ERR_NOT_ENOUGH_SERVICE_FEE");
+      self.total_service_fee.insert(&token_id, &(balance - amount.0));
+
+      let withdrawal_index = self.service_fee_withdrawals.len();
+      self.service_fee_withdrawals.push(&ServiceFeeWithdrawal {
+         token_id: token_id.clone(),
+         amount,
+         receiver_id: receiver_id.clone(),
+         timestamp: env::block_timestamp().into(),
+         status: WithdrawalStatus::Pending,
+      });
+
+      log!("Withdrawing service fee {} to @{}", amount.0, receiver_id);
+
+      let transfer = if let Some(token_id) = token_id {
+         ext_ft::ft_transfer(
+            receiver_id,
+            amount,
+            Some("Giveaway service fee".to_string()),
+            token_id,
+            ONE_YOCTO,
+            GAS_FOR_FT_TRANSFER)
+      } else {
+         Promise::new(receiver_id).transfer(amount.0)
+      };
+
+      transfer.then(ext_self::after_withdraw_service_fee(
+         withdrawal_index,
+         env::current_account_id(),
+         NO_DEPOSIT,
+         GAS_FOR_AFTER_WITHDRAW_SERVICE_FEE,
+      ))
+   }
+
+   #[private]
+   pub fn after_withdraw_service_fee(&mut self, withdrawal_index: u64) -> bool {
+      let promise_success = is_promise_success();
+      let mut withdrawal = self.service_fee_withdrawals.get(withdrawal_index).expect("ERR_NO_WITHDRAWAL");
+      if promise_success {
+         withdrawal.status = WithdrawalStatus::Complete;
+      } else {
+         log!("Service fee withdrawal {} failed", withdrawal_index);
+         withdrawal.status = WithdrawalStatus::Failed;
+         self.internal_add_service_fee(&withdrawal.token_id, &withdrawal.amount.0);
+      }
+      self.service_fee_withdrawals.replace(withdrawal_index, &withdrawal);
+
+      promise_success
+   }
+
+   pub fn get_service_fee_withdrawals(&self, from_index: u64, limit: u64) -> Vec<ServiceFeeWithdrawal> {
+      (from_index..std::cmp::min(from_index + limit, self.service_fee_withdrawals.len()))
+         .filter_map(|index| self.service_fee_withdrawals.get(index))
+         .collect()
+   }
+}
@@ -0,0 +1,59 @@
+use near_sdk::StorageUsage;
+
+use crate::*;
+
+/// Cost of the storage used since `initial_storage_usage`
+pub fn get_storage_cost(initial_storage_usage: StorageUsage) -> Balance {
+   Balance::from(env::storage_usage().saturating_sub(initial_storage_usage)) * env::storage_byte_cost()
+}
+
+impl Giveaway {
+   /// Pays for storage from the NEAR storage deposit of the account, used where no NEAR is attached
+   pub(crate) fn internal_charge_storage_deposit(&mut self, account_id: &AccountId, storage_cost: Balance) {
+      if storage_cost == 0 {
+         return;
+      }
+      let balance = self.storage_deposits.get(account_id).unwrap_or_default();
+      assert!(
+         balance >= storage_cost,
+         "Not enough storage deposit (Balance: {}, Required: {}), use deposit_storage",
+         balance, storage_cost
+      );
+      self.storage_deposits.insert(account_id, &(balance - storage_cost));
+   }
+}
+
+#[near_bindgen]
+impl Giveaway {
+   /// Prepays NEAR for the storage of the account, e.g. for tickets bought with fungible tokens.
+   /// The storage of the deposit record is paid from the deposit. Returns the new balance
+   #[payable]
+   pub fn deposit_storage(&mut self, account_id: Option<AccountId>) -> WrappedBalance {
+      let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+      let initial_storage_usage = env::storage_usage();
+      let balance = self.storage_deposits.get(&account_id).unwrap_or_default();
+      self.storage_deposits.insert(&account_id, &balance);
+
+      let deposit = env::attached_deposit();
+      let storage_cost = get_storage_cost(initial_storage_usage);
+      assert!(deposit > storage_cost, "Not enough deposit to pay for storage (Attached: {}, Required: {})", deposit, storage_cost);
+
+      let balance = balance + deposit - storage_cost;
+      self.storage_deposits.insert(&account_id, &balance);
+      balance.into()
+   }
+
+   /// Returns the unused storage deposit of the caller
+   pub fn withdraw_storage(&mut self) -> WrappedBalance {
+      let account_id = env::predecessor_account_id();
+      let balance = self.storage_deposits.remove(&account_id).expect("No storage deposit");
+      if balance > 0 {
+         Promise::new(account_id).transfer(balance);
+      }
+      balance.into()
+   }
+
+   pub fn get_storage_deposit(&self, account_id: AccountId) -> WrappedBalance {
+      self.storage_deposits.get(&account_id).unwrap_or_default().into()
+   }
+}
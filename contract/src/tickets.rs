@@ -0,0 +1,213 @@
+use near_sdk::collections::Vector;
+use near_sdk::IntoStorageKey;
+
+use crate::*;
+
+pub type TicketsNumber = u64;
+pub type WrappedTicketsNumber = U64;
+
+/// Ticket counts of event participants, indexed the same way as `Event.participants`.
+/// A Fenwick tree over counts of participants still in the draw finds the holder
+/// of a ticket number in O(log n) storage reads.
+#[derive(BorshSerialize, BorshDeserialize)]
+pub struct Tickets {
+   /// tickets of every participant
+   counts: Vector<TicketsNumber>,
+   /// Fenwick tree of tickets of participants who haven't won yet
+   tree: Vector<TicketsNumber>,
+   /// total number of tickets
+   total: TicketsNumber,
+   /// number of tickets of participants who haven't won yet
+   remaining: TicketsNumber,
+   /// number of participants with tickets
+   holders: u64,
+}
+
+impl Tickets {
+   pub fn new<S: IntoStorageKey>(counts_prefix: S, tree_prefix: S) -> Self {
+      Self {
+         counts: Vector::new(counts_prefix),
+         tree: Vector::new(tree_prefix),
+         total: 0,
+         remaining: 0,
+         holders: 0,
+      }
+   }
+
+   pub fn get(&self, index: u64) -> TicketsNumber {
+      self.counts.get(index).unwrap_or_default()
+   }
+
+   pub fn get_total(&self) -> TicketsNumber {
+      self.total
+   }
+
+   pub fn get_remaining(&self) -> TicketsNumber {
+      self.remaining
+   }
+
+   pub fn get_holders(&self) -> u64 {
+      self.holders
+   }
+
+   /// Registers tickets of a new participant
+   pub fn push(&mut self, tickets: TicketsNumber) {
+      self.counts.push(&tickets);
+
+      // The new node covers itself and the previous nodes down to `position - lowbit(position)`
+      let position = self.counts.len();
+      let covered = self.prefix_sum(position - 1) - self.prefix_sum(position - lowbit(position));
+      self.tree.push(&(tickets + covered));
+
+      self.total += tickets;
+      self.remaining += tickets;
+      if tickets > 0 {
+         self.holders += 1;
+      }
+   }
+
+   /// Changes tickets of the existing participant, without tickets the participant leaves the draw
+   pub fn set(&mut self, index: u64, tickets: TicketsNumber) {
+      let count = self.counts.get(index).expect("ERR_NO_PARTICIPANT");
+      self.counts.replace(index, &tickets);
+      self.tree_add(index, tickets as i128 - count as i128);
+
+      self.total = self.total + tickets - count;
+      self.remaining = self.remaining + tickets - count;
+      if count == 0 && tickets > 0 {
+         self.holders += 1;
+      } else if count > 0 && tickets == 0 {
+         self.holders -= 1;
+      }
+   }
+
+   /// Excludes all tickets of the participant from further draws
+   pub fn remove_from_draw(&mut self, index: u64) {
+      let count = self.get(index);
+      self.tree_add(index, -(count as i128));
+      self.remaining -= count;
+   }
+
+   /// Index of the participant holding `ticket`, counting tickets in participants order
+   /// and skipping participants removed from the draw. `ticket` should be less than `get_remaining()`
+   pub fn find(&self, ticket: TicketsNumber) -> u64 {
+      assert!(ticket < self.remaining, "ERR_WRONG_TICKET");
+      let len = self.tree.len();
+      let mut position: u64 = 0;
+      let mut rest = ticket;
+      let mut step = if len == 0 { 0 } else { 1u64 << (63 - len.leading_zeros()) };
+      while step > 0 {
+         let next = position + step;
+         if next <= len {
+            let node = self.tree.get(next - 1).unwrap();
+            if node <= rest {
+               position = next;
+               rest -= node;
+            }
+         }
+         step >>= 1;
+      }
+      position
+   }
+
+   /// Tickets in the draw of the first `position` participants
+   fn prefix_sum(&self, mut position: u64) -> TicketsNumber {
+      let mut sum = 0;
+      while position > 0 {
+         sum += self.tree.get(position - 1).unwrap();
+         position -= lowbit(position);
+      }
+      sum
+   }
+
+   fn tree_add(&mut self, index: u64, delta: i128) {
+      let len = self.tree.len();
+      let mut position = index + 1;
+      while position <= len {
+         let node = self.tree.get(position - 1).unwrap();
+         self.tree.replace(position - 1, &((node as i128 + delta) as TicketsNumber));
+         position += lowbit(position);
+      }
+   }
+}
+
+fn lowbit(position: u64) -> u64 {
+   position & position.wrapping_neg()
+}
+
+#[cfg(test)]
+mod tests {
+   use near_sdk::test_utils::VMContextBuilder;
+   use near_sdk::testing_env;
+
+   use super::*;
+
+   fn get_tickets(counts: &[TicketsNumber]) -> Tickets {
+      testing_env!(VMContextBuilder::new().build());
+      let mut tickets = Tickets::new(b"c".to_vec(), b"t".to_vec());
+      for count in counts {
+         tickets.push(*count);
+      }
+      tickets
+   }
+
+   #[test]
+   fn test_find_at_bucket_boundaries() {
+      let tickets = get_tickets(&[1, 0, 3, 2, 5, 1]);
+      assert_eq!(tickets.get_remaining(), 12);
+
+      let holders: Vec<u64> = (0..12).map(|ticket| tickets.find(ticket)).collect();
+      assert_eq!(holders, vec![0, 2, 2, 2, 3, 3, 4, 4, 4, 4, 4, 5]);
+   }
+
+   #[test]
+   fn test_find_across_tree_levels() {
+      // Nodes of 1, 2, 4 and 8 participants are crossed
+      let tickets = get_tickets(&[1; 17]);
+      for ticket in 0..17 {
+         assert_eq!(tickets.find(ticket), ticket);
+      }
+   }
+
+   #[test]
+   fn test_remove_from_draw() {
+      let mut tickets = get_tickets(&[1, 0, 3, 2, 5, 1]);
+      tickets.remove_from_draw(2);
+      assert_eq!(tickets.get_remaining(), 9);
+      assert_eq!(tickets.get_total(), 12);
+
+      let holders: Vec<u64> = (0..9).map(|ticket| tickets.find(ticket)).collect();
+      assert_eq!(holders, vec![0, 3, 3, 4, 4, 4, 4, 4, 5]);
+
+      tickets.remove_from_draw(0);
+      tickets.remove_from_draw(5);
+      assert_eq!(tickets.find(0), 3);
+      assert_eq!(tickets.find(6), 4);
+   }
+
+   #[test]
+   #[should_panic(expected = "ERR_WRONG_TICKET")]
+   fn test_find_after_all_removed() {
+      let mut tickets = get_tickets(&[2, 1]);
+      tickets.remove_from_draw(0);
+      tickets.remove_from_draw(1);
+      tickets.find(0);
+   }
+
+   #[test]
+   fn test_zero_ticket_holder() {
+      let mut tickets = get_tickets(&[0, 2, 0]);
+      assert_eq!(tickets.get_holders(), 1);
+      assert_eq!(tickets.find(0), 1);
+      assert_eq!(tickets.find(1), 1);
+
+      // The only holder leaves and a participant without tickets joins again
+      tickets.set(1, 0);
+      assert_eq!(tickets.get_holders(), 0);
+      assert_eq!(tickets.get_remaining(), 0);
+      tickets.set(2, 3);
+      assert_eq!(tickets.get_holders(), 1);
+      assert_eq!(tickets.find(0), 2);
+      assert_eq!(tickets.find(2), 2);
+   }
+}
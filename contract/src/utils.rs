@@ -1,3 +1,5 @@
+use near_sdk::PromiseResult;
+
 use crate::*;
 
 impl Giveaway {
@@ -12,6 +14,22 @@ impl Giveaway {
       }
    }
 
+   pub fn assert_whitelisted_nft_contract(&self, contract_id: &AccountId) {
+      assert!(self.is_whitelisted_nft_contract(contract_id), "ERR_NFT_CONTRACT_NOT_ALLOWED");
+   }
+
+   /// Minimal deposit is set per whitelisted token, `MIN_DEPOSIT_AMOUNT` for NEAR. It's also the minimal ticket price
+   pub fn get_min_deposit(&self, token_id: &Option<TokenId>) -> Balance {
+      match token_id {
+         Some(token_id_value) => self.whitelisted_tokens.get(token_id_value).expect("ERR_TOKEN_NOT_ALLOWED").min_deposit.0,
+         None => MIN_DEPOSIT_AMOUNT,
+      }
+   }
+
+   pub fn assert_min_deposit(&self, token_id: &Option<TokenId>, amount: Balance) {
+      assert!(amount >= self.get_min_deposit(token_id), "Not enough deposit");
+   }
+
    pub fn assert_event_owner(&self, event: &Event) {
       assert_eq!(env::predecessor_account_id(), event.owner_account_id, "ERR_NO_ACCESS");
    }
@@ -25,16 +43,49 @@ impl Giveaway {
    }
 }
 
-pub fn get_service_fee(total: &Balance) -> Balance{
-   std::cmp::min(MAX_SERVICE_FEE, total * SERVICE_FEE_NUMERATOR as Balance / SERVICE_FEE_DENOMINATOR as Balance)
+pub fn assert_participants_update_allowed(event: &Event) {
+   assert_eq!(event.status, EventStatus::Pending, "Already finalized");
+
+   let current_timestamp: Duration = env::block_timestamp();
+   assert!(current_timestamp >= event.add_participants_start_timestamp.0, "It's too early to add participants");
+   assert!(current_timestamp < event.add_participants_end_timestamp.0, "It's too late to add participants");
+   assert!(current_timestamp < event.event_timestamp.0, "Event date already passed");
+}
+
+pub fn assert_valid_reward_label(label: &str) {
+   assert!(label.len() < MAX_REWARD_LABEL_LENGTH, "Reward label is too long");
+}
+
+pub fn is_promise_success() -> bool {
+   assert_eq!(
+      env::promise_results_count(),
+      1,
+      "Contract expected a result on the callback"
+   );
+   match env::promise_result(0) {
+      PromiseResult::Successful(_) => true,
+      _ => false,
+   }
 }
 
 #[near_bindgen]
 impl Giveaway {
+   /// Pause guardians can pause the contract, only the owner can resume it
    pub fn set_active(&mut self, active: bool) {
+      if active {
+         self.assert_contract_owner();
+         assert!(self.migration.is_none(), "Events are not migrated yet");
+      } else {
+         self.assert_role(Role::PauseGuardian);
+      }
+
       self.active = active;
    }
 
+   pub fn is_active(&self) -> bool {
+      self.active
+   }
+
    pub fn get_next_event_id(&self) -> u64 {
       self.next_event_id
    }
@@ -43,8 +94,4 @@ impl Giveaway {
       self.total_service_fee.get(&token_id)
    }
 
-   pub fn get_service_fee_percent(&self) -> f64{
-      SERVICE_FEE_NUMERATOR as f64 / SERVICE_FEE_DENOMINATOR as f64
-   }
-
 }
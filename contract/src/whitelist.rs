@@ -1,14 +1,81 @@
 use crate::*;
 
+#[derive(BorshSerialize, BorshDeserialize, Serialize, Deserialize, Clone, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WhitelistedToken {
+   pub symbol: String,
+   pub decimals: u8,
+   /// minimal amount of tokens to create an event
+   pub min_deposit: WrappedBalance,
+   /// storage deposit needed to register a reward recipient on the token contract
+   pub storage_deposit: WrappedBalance,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct WhitelistedTokenOutput {
+   pub token_id: TokenId,
+   #[serde(flatten)]
+   pub metadata: WhitelistedToken,
+}
+
 #[near_bindgen]
 impl Giveaway {
-   pub fn whitelist_token(&mut self, token_id: TokenId) {
-      self.assert_contract_owner();
+   /// Adds the token to the whitelist or updates its metadata
+   pub fn whitelist_token(&mut self, token_id: TokenId, metadata: WhitelistedToken) {
+      self.assert_role(Role::FeeManager);
 
-      self.whitelisted_tokens.insert(&token_id);
+      self.whitelisted_tokens.insert(&token_id, &metadata);
+   }
+
+   /// Events with the token can't be created anymore, existing ones are paid out as usual
+   pub fn remove_whitelisted_token(&mut self, token_id: TokenId) {
+      self.assert_role(Role::FeeManager);
+
+      assert!(self.whitelisted_tokens.remove(&token_id).is_some(), "ERR_TOKEN_NOT_FOUND");
    }
 
    pub fn is_whitelisted_token(&self, token_id: &TokenId) -> bool {
-      self.whitelisted_tokens.contains(token_id)
+      self.whitelisted_tokens.get(token_id).is_some()
+   }
+
+   pub fn get_whitelisted_token(&self, token_id: TokenId) -> Option<WhitelistedToken> {
+      self.whitelisted_tokens.get(&token_id)
+   }
+
+   pub fn get_whitelisted_tokens(&self, from_index: u64, limit: u64) -> Vec<WhitelistedTokenOutput> {
+      let keys = self.whitelisted_tokens.keys_as_vector();
+      (from_index..std::cmp::min(from_index + limit, keys.len()))
+         .filter_map(|index| keys.get(index))
+         .map(|token_id| WhitelistedTokenOutput {
+            metadata: self.whitelisted_tokens.get(&token_id).unwrap(),
+            token_id,
+         })
+         .collect()
+   }
+
+   /// Allows NFT prizes and events created with nft_transfer_call from the contract
+   pub fn whitelist_nft_contract(&mut self, contract_id: AccountId) {
+      self.assert_role(Role::FeeManager);
+
+      self.whitelisted_nft_contracts.insert(&contract_id);
+   }
+
+   /// NFTs of the contract can't be added anymore, escrowed ones are paid out as usual
+   pub fn remove_whitelisted_nft_contract(&mut self, contract_id: AccountId) {
+      self.assert_role(Role::FeeManager);
+
+      assert!(self.whitelisted_nft_contracts.remove(&contract_id), "ERR_NFT_CONTRACT_NOT_FOUND");
+   }
+
+   pub fn is_whitelisted_nft_contract(&self, contract_id: &AccountId) -> bool {
+      self.whitelisted_nft_contracts.contains(contract_id)
+   }
+
+   pub fn get_whitelisted_nft_contracts(&self, from_index: u64, limit: u64) -> Vec<AccountId> {
+      let contracts = self.whitelisted_nft_contracts.as_vector();
+      (from_index..std::cmp::min(from_index + limit, contracts.len()))
+         .filter_map(|index| contracts.get(index))
+         .collect()
    }
 }
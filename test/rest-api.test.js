@@ -33,6 +33,16 @@ describe("Contract set", () => {
         }, {account_id: contract_id, log_errors: false});
     });
 
+    test("Only pause guardians can pause", async () => {
+        const set_active = await near.call("set_active", {
+            active: false
+        }, {account_id: alice, log_errors: false});
+        expect(set_active.type).toBe('FunctionCallError');
+
+        const is_active = await near.view("is_active", {}, {parse_json: true});
+        expect(is_active).toBe(true);
+    });
+
     test('Accounts has enough funds', async () => {
         const alice_wallet_balance = await near.accountNearBalance(alice);
         expect(alice_wallet_balance).toBeGreaterThan(20);
@@ -177,6 +187,138 @@ describe("Create events", () => {
         expect(event_4.status).toBe('Distributed');
     });
 
+    test("Cancel event", async () => {
+        const event_id = await near.call("add_event", {
+            event_input: {
+                rewards: [
+                    utils.ConvertToNear(reward_3)
+                ],
+                rewards_token_id: null,
+                participants: [
+                    alice,
+                    bob,
+                    carol],
+                allow_duplicate_participants: false,
+                add_participants_start_timestamp: start_timestamp,
+                add_participants_end_timestamp: end_timestamp,
+                event_timestamp: event_timestamp,
+                title: "Test",
+                description: "Test text"
+            }
+        }, {
+            account_id: admin,
+            deposit_near: reward_3 + service_fee,
+            return_value_int: true
+        });
+
+        const cancel_event_1 = await near.call("cancel_event", {
+            event_id: parseInt(event_id)
+        }, {account_id: alice, log_errors: false});
+        expect(cancel_event_1.type).toBe('FunctionCallError');
+
+        const admin_wallet_balance_1 = await near.accountNearBalance(admin);
+
+        const cancel_event_2 = await near.call("cancel_event", {
+            event_id: parseInt(event_id)
+        }, {account_id: admin});
+        expect(cancel_event_2.type).not.toBe('FunctionCallError');
+
+        const admin_wallet_balance_2 = await near.accountNearBalance(admin);
+        expect(admin_wallet_balance_2).toBeGreaterThan(admin_wallet_balance_1);
+
+        const event = await near.view("get_event",
+            {event_id: parseInt(event_id)}, {parse_json: true});
+        expect(event.status).toBe('Cancelled');
+
+        const finalize_event = await near.call("finalize_event", {
+            event_id: parseInt(event_id)
+        }, {account_id: admin, log_errors: false});
+        expect(finalize_event.type).toBe('FunctionCallError');
+    });
+
+    test("Join and leave event", async () => {
+        const event_id = await near.call("add_event", {
+            event_input: {
+                rewards: [
+                    utils.ConvertToNear(reward_3)
+                ],
+                rewards_token_id: null,
+                participants: [],
+                allow_duplicate_participants: false,
+                open_registration: true,
+                max_participants: "2",
+                add_participants_start_timestamp: start_timestamp,
+                add_participants_end_timestamp: end_timestamp,
+                event_timestamp: end_timestamp,
+                title: "Test",
+                description: "Test text"
+            }
+        }, {
+            account_id: admin,
+            deposit_near: reward_3 + service_fee,
+            return_value_int: true
+        });
+
+        const join_event_1 = await near.call("join_event", {event_id}, {account_id: alice, deposit_near: 0.01});
+        expect(join_event_1.type).not.toBe('FunctionCallError');
+
+        const join_event_2 = await near.call("join_event", {event_id}, {account_id: alice, deposit_near: 0.01, log_errors: false});
+        expect(join_event_2.type).toBe('FunctionCallError');
+
+        const join_event_3 = await near.call("join_event", {event_id}, {account_id: bob, deposit_near: 0.01});
+        expect(join_event_3.type).not.toBe('FunctionCallError');
+
+        const join_event_4 = await near.call("join_event", {event_id}, {account_id: carol, deposit_near: 0.01, log_errors: false});
+        expect(join_event_4.type).toBe('FunctionCallError');
+
+        const leave_event = await near.call("leave_event", {event_id}, {account_id: bob});
+        expect(leave_event.type).not.toBe('FunctionCallError');
+
+        const join_event_5 = await near.call("join_event", {event_id}, {account_id: carol, log_errors: false});
+        expect(join_event_5.type).toBe('FunctionCallError');
+
+        const event = await near.view("get_event", {event_id}, {parse_json: true});
+        expect(event.participants).toEqual([alice]);
+    });
+
+    test("Finalize and distribute", async () => {
+        const event_id = await near.call("add_event", {
+            event_input: {
+                rewards: [
+                    utils.ConvertToNear(reward_3)
+                ],
+                rewards_token_id: null,
+                participants: [
+                    alice
+                ],
+                allow_duplicate_participants: false,
+                add_participants_start_timestamp: start_timestamp,
+                add_participants_end_timestamp: end_timestamp,
+                event_timestamp: event_timestamp,
+                title: "Test",
+                description: "Test text"
+            }
+        }, {
+            account_id: admin,
+            deposit_near: reward_3 + service_fee,
+            return_value_int: true
+        });
+
+        const alice_wallet_balance_1 = await near.accountNearBalance(alice);
+
+        const finalize_and_distribute = await near.call("finalize_and_distribute", {
+            event_id: parseInt(event_id)
+        }, {account_id: admin});
+        expect(finalize_and_distribute.type).not.toBe('FunctionCallError');
+
+        const alice_wallet_balance_2 = await near.accountNearBalance(alice);
+        expect(alice_wallet_balance_2 - alice_wallet_balance_1).toBeCloseTo(reward_3);
+
+        const event = await near.view("get_event",
+            {event_id: parseInt(event_id)}, {parse_json: true});
+        expect(event.status).toBe('Distributed');
+    });
+
     test("2 participants, 2 rewards", async () => {
         const event_id = await near.call("add_event", {
             event_input: {
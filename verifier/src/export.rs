@@ -0,0 +1,117 @@
+//! JSON export of an event: `get_event`, `get_participants` and `get_payouts` results of the contract.
+
+use serde::{Deserialize, Deserializer};
+
+#[derive(Deserialize)]
+pub struct Export {
+    pub event: EventExport,
+    /// all participants of the event in the contract order, including ones without tickets
+    pub participants: Vec<ParticipantExport>,
+    /// payouts to compare with the replayed draw
+    #[serde(default)]
+    pub payouts: Vec<PayoutExport>,
+}
+
+#[derive(Deserialize)]
+pub struct EventExport {
+    pub event_id: u64,
+    pub owner_account_id: String,
+    #[serde(deserialize_with = "from_strings")]
+    pub rewards: Vec<u128>,
+    pub reward_labels: Vec<Option<String>>,
+    #[serde(default)]
+    pub pool_tiers: Vec<u32>,
+    #[serde(default)]
+    pub nft_rewards: Vec<NftRewardExport>,
+    #[serde(default, deserialize_with = "from_string")]
+    pub reserve_winners_number: u64,
+    #[serde(default)]
+    pub reserve_winners: Vec<String>,
+    /// length of `get_participants`, checked against the exported participants if present
+    #[serde(default, deserialize_with = "from_optional_string")]
+    pub participants_number: Option<u64>,
+    pub draw: Option<DrawExport>,
+}
+
+#[derive(Deserialize, Clone)]
+pub struct NftRewardExport {
+    pub contract_id: String,
+    pub token_id: String,
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DrawExport {
+    /// base64 encoded seed
+    pub random_seed: String,
+    pub algorithm_version: u8,
+    #[serde(deserialize_with = "from_string")]
+    pub counter: u64,
+    /// number of assigned prizes
+    #[serde(deserialize_with = "from_string")]
+    pub cursor: u64,
+    #[serde(deserialize_with = "from_string")]
+    pub prize_pool: u128,
+}
+
+#[derive(Deserialize)]
+pub struct ParticipantExport {
+    pub account_id: String,
+    #[serde(deserialize_with = "from_string")]
+    pub tickets: u64,
+}
+
+#[derive(Deserialize)]
+pub struct PayoutExport {
+    pub account_id: String,
+    #[serde(default)]
+    pub refund: bool,
+    /// previous receivers of the prize, the first one is the drawn winner
+    #[serde(default)]
+    pub redraws: Vec<RedrawExport>,
+}
+
+impl PayoutExport {
+    /// Winner of the draw before redraws, None for unawarded prizes
+    pub fn get_drawn_account_id(&self) -> Option<&str> {
+        match self.redraws.first() {
+            Some(redraw) => Some(&redraw.account_id),
+            None if self.refund => None,
+            None => Some(&self.account_id),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RedrawExport {
+    pub account_id: String,
+}
+
+/// U64 and U128 of the contract are serialized as strings
+fn from_string<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    let value = String::deserialize(deserializer)?;
+    value.parse().map_err(|_| serde::de::Error::custom(format!("Invalid number {}", value)))
+}
+
+fn from_optional_string<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    from_string(deserializer).map(Some)
+}
+
+fn from_strings<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: std::str::FromStr,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .into_iter()
+        .map(|value| value.parse().map_err(|_| serde::de::Error::custom(format!("Invalid number {}", value))))
+        .collect()
+}
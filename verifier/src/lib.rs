@@ -0,0 +1,157 @@
+//! Offline verifier of giveaway draws. Replays the winners selection of `finalize_event`
+//! from a JSON export of the event and compares the winners with the payouts and reserve winners.
+
+use crate::export::*;
+use crate::random::{draw_winners, DrawRng};
+
+pub mod export;
+pub mod random;
+
+/// Version of the contract draw algorithm reproduced by this crate
+pub const DRAW_ALGORITHM_VERSION: u8 = 1;
+const BASIS_POINTS: u32 = 10_000;
+
+pub struct Prize {
+    pub amount: u128,
+    pub label: Option<String>,
+    pub nft: Option<NftRewardExport>,
+}
+
+pub struct Winner {
+    /// 1-based position of the prize in the event
+    pub place: u64,
+    pub account_id: String,
+    /// unawarded prize returned to the event owner
+    pub refund: bool,
+    pub prize: Prize,
+}
+
+pub struct Mismatch {
+    pub payout_index: usize,
+    pub expected: Option<String>,
+    pub actual: Option<String>,
+}
+
+pub struct Verification {
+    pub winners: Vec<Winner>,
+    pub reserve_winners: Vec<String>,
+    /// payouts which don't match the replayed draw, redrawn payouts are compared by their first winner
+    pub mismatches: Vec<Mismatch>,
+    pub reserve_winners_match: bool,
+    /// number of hashes used by the replay, it should match `counter` of the draw
+    pub counter: u64,
+    pub counter_matches: bool,
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        self.mismatches.is_empty() && self.reserve_winners_match && self.counter_matches
+    }
+}
+
+/// Fungible rewards followed by shares of the prize pool and NFT prizes, in the order they are drawn
+pub fn get_prizes(event: &EventExport, prize_pool: u128) -> Vec<Prize> {
+    let mut pool_prizes: Vec<u128> = if prize_pool > 0 {
+        event.pool_tiers.iter().map(|tier| prize_pool * *tier as u128 / BASIS_POINTS as u128).collect()
+    } else {
+        vec![]
+    };
+    let distributed: u128 = pool_prizes.iter().sum();
+    if let Some(first_prize) = pool_prizes.first_mut() {
+        *first_prize += prize_pool - distributed;
+    }
+
+    event.rewards.iter().enumerate()
+        .map(|(index, amount)| Prize {
+            amount: *amount,
+            label: event.reward_labels.get(index).cloned().flatten(),
+            nft: None,
+        })
+        .chain(pool_prizes.into_iter().map(|amount| Prize { amount, label: None, nft: None }))
+        .chain(event.nft_rewards.iter().map(|nft| Prize { amount: 0, label: nft.label.clone(), nft: Some(nft.clone()) }))
+        .collect()
+}
+
+/// Replays the assigned prizes of the draw and compares winners with `export.payouts`
+pub fn verify(export: &Export) -> Result<Verification, String> {
+    let event = &export.event;
+    let draw = event.draw.as_ref().ok_or("The event has no draw yet")?;
+    if draw.algorithm_version != DRAW_ALGORITHM_VERSION {
+        return Err(format!("Unsupported draw algorithm version {}", draw.algorithm_version));
+    }
+    let random_seed = base64::decode(&draw.random_seed).map_err(|e| format!("Invalid random seed: {}", e))?;
+
+    if let Some(participants_number) = event.participants_number {
+        if participants_number != export.participants.len() as u64 {
+            return Err(format!(
+                "The export has {} participants, the event has {}",
+                export.participants.len(),
+                participants_number
+            ));
+        }
+    }
+
+    let prizes = get_prizes(event, draw.prize_pool);
+    let draw_size = prizes.len() as u64 + event.reserve_winners_number;
+    if draw.cursor > draw_size {
+        return Err(format!("{} winners drawn out of {}", draw.cursor, draw_size));
+    }
+
+    let tickets: Vec<u64> = export.participants.iter().map(|participant| participant.tickets).collect();
+    let mut rng = DrawRng::new(random_seed, event.event_id);
+    let mut drawn = draw_winners(&mut rng, &tickets, draw.cursor);
+    // Reserve winners are drawn after all prizes
+    let reserves = drawn.split_off(std::cmp::min(prizes.len(), drawn.len()));
+    let reserve_winners: Vec<String> = reserves.into_iter()
+        .flatten()
+        .map(|reserve_index| export.participants[reserve_index].account_id.clone())
+        .collect();
+    let winners: Vec<Winner> = drawn.into_iter()
+        .zip(prizes)
+        .enumerate()
+        .map(|(prize_index, (winner_index, prize))| Winner {
+            place: prize_index as u64 + 1,
+            account_id: match winner_index {
+                Some(winner_index) => export.participants[winner_index].account_id.clone(),
+                None => event.owner_account_id.clone(),
+            },
+            refund: winner_index.is_none(),
+            prize,
+        })
+        .collect();
+    let counter = rng.get_counter();
+
+    let mismatches = (0..std::cmp::max(winners.len(), export.payouts.len()))
+        .filter_map(|payout_index| {
+            let winner = winners.get(payout_index);
+            let payout = export.payouts.get(payout_index);
+            let matches = match (winner, payout) {
+                (Some(winner), Some(payout)) => match payout.get_drawn_account_id() {
+                    Some(account_id) => !winner.refund && winner.account_id == account_id,
+                    None => winner.refund,
+                },
+                _ => false,
+            };
+            if matches {
+                None
+            } else {
+                Some(Mismatch {
+                    payout_index,
+                    expected: winner.map(|winner| winner.account_id.clone()),
+                    actual: payout.map(|payout| payout.get_drawn_account_id().unwrap_or(&event.owner_account_id).to_string()),
+                })
+            }
+        })
+        .collect();
+
+    let reserve_winners_match = reserve_winners == event.reserve_winners;
+
+    Ok(Verification {
+        winners,
+        reserve_winners,
+        mismatches,
+        reserve_winners_match,
+        counter,
+        counter_matches: counter == draw.counter,
+    })
+}
@@ -0,0 +1,64 @@
+//! `giveaway-verify [export.json]` prints winners of the draw and exits with 1 if they don't match payouts.
+//! Reads the export from stdin without arguments, see README.md for the format.
+
+use std::io::Read;
+use std::process::exit;
+
+use giveaway_verify::export::Export;
+use giveaway_verify::verify;
+
+fn main() {
+    let input = match std::env::args().nth(1) {
+        Some(path) => std::fs::read_to_string(&path).unwrap_or_else(|e| fail(&format!("Can't read {}: {}", path, e))),
+        None => {
+            let mut input = String::new();
+            std::io::stdin().read_to_string(&mut input).unwrap_or_else(|e| fail(&format!("Can't read stdin: {}", e)));
+            input
+        }
+    };
+    let export: Export = serde_json::from_str(&input).unwrap_or_else(|e| fail(&format!("Invalid export: {}", e)));
+    let verification = verify(&export).unwrap_or_else(|e| fail(&e));
+
+    for winner in &verification.winners {
+        let prize = match &winner.prize.nft {
+            Some(nft) => format!("NFT {} of {}", nft.token_id, nft.contract_id),
+            None => winner.prize.amount.to_string(),
+        };
+        let label = winner.prize.label.as_ref().map(|label| format!(" ({})", label)).unwrap_or_default();
+        let refund = if winner.refund { " [refund]" } else { "" };
+        println!("#{} @{} {}{}{}", winner.place, winner.account_id, prize, label, refund);
+    }
+
+    for (index, account_id) in verification.reserve_winners.iter().enumerate() {
+        println!("Reserve #{} @{}", index + 1, account_id);
+    }
+
+    for mismatch in &verification.mismatches {
+        println!(
+            "Mismatch in payout {}: expected {}, found {}",
+            mismatch.payout_index,
+            mismatch.expected.as_deref().unwrap_or("no payout"),
+            mismatch.actual.as_deref().unwrap_or("no payout")
+        );
+    }
+
+    if !verification.reserve_winners_match {
+        println!("Reserve winners don't match the event");
+    }
+
+    if !verification.counter_matches {
+        println!("The draw used {} hashes, replay used {}", export.event.draw.as_ref().unwrap().counter, verification.counter);
+    }
+
+    if verification.is_valid() {
+        println!("Draw verified: {} prizes match the payouts", verification.winners.len());
+    } else {
+        println!("Draw verification failed");
+        exit(1);
+    }
+}
+
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    exit(2);
+}
@@ -0,0 +1,73 @@
+//! Winner selection of `finalize_event`, see `contract/src/random.rs` for the description.
+
+use sha2::{Digest, Sha256};
+
+pub type EventId = u64;
+pub type TicketsNumber = u64;
+
+pub struct DrawRng {
+    seed: Vec<u8>,
+    event_id: EventId,
+    counter: u64,
+}
+
+impl DrawRng {
+    pub fn new(seed: Vec<u8>, event_id: EventId) -> Self {
+        Self {
+            seed,
+            event_id,
+            counter: 0,
+        }
+    }
+
+    pub fn get_counter(&self) -> u64 {
+        self.counter
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        let mut hasher = Sha256::new();
+        hasher.update(&self.seed);
+        hasher.update(self.event_id.to_le_bytes());
+        hasher.update(self.counter.to_le_bytes());
+        self.counter += 1;
+
+        let hash = hasher.finalize();
+        let mut value = [0u8; 8];
+        value.copy_from_slice(&hash[..8]);
+        u64::from_le_bytes(value)
+    }
+
+    /// Uniformly distributed index in `0..n`
+    pub fn next_index(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "ERR_EMPTY_RANGE");
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let value = self.next_u64();
+            if value < zone {
+                return value % n;
+            }
+        }
+    }
+}
+
+/// Indexes of winners of `prizes_number` prizes, None for prizes returned to the event owner.
+/// `tickets` are tickets of participants in the order of `get_participants`
+pub fn draw_winners(rng: &mut DrawRng, tickets: &[TicketsNumber], prizes_number: u64) -> Vec<Option<usize>> {
+    let mut remaining_tickets = tickets.to_vec();
+    let mut remaining: TicketsNumber = tickets.iter().sum();
+    (0..prizes_number).map(|_| {
+        if remaining == 0 {
+            return None;
+        }
+        // Plain scan instead of the Fenwick tree of the contract, both find the holder of the same ticket
+        let mut ticket = rng.next_index(remaining);
+        let mut winner_index = 0;
+        while ticket >= remaining_tickets[winner_index] {
+            ticket -= remaining_tickets[winner_index];
+            winner_index += 1;
+        }
+        remaining -= remaining_tickets[winner_index];
+        remaining_tickets[winner_index] = 0;
+        Some(winner_index)
+    }).collect()
+}
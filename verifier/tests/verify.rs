@@ -0,0 +1,80 @@
+//! Replays draws exported from the contract, where hashes come from `env::sha256`.
+
+use giveaway_verify::export::Export;
+use giveaway_verify::verify;
+
+fn load(fixture: &str) -> Export {
+    let path = format!("{}/tests/fixtures/{}", env!("CARGO_MANIFEST_DIR"), fixture);
+    serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+}
+
+fn winners(export: &Export) -> Vec<(String, bool)> {
+    verify(export).unwrap().winners.into_iter()
+        .map(|winner| (winner.account_id, winner.refund))
+        .collect()
+}
+
+#[test]
+fn test_reserve_winners() {
+    let export = load("reserve_winners.json");
+    let verification = verify(&export).unwrap();
+
+    assert!(verification.is_valid());
+    assert_eq!(verification.counter, 5);
+    assert_eq!(verification.reserve_winners, vec!["f.near", "a.near"]);
+    assert_eq!(winners(&export), vec![
+        ("c.near".to_string(), false),
+        ("e.near".to_string(), false),
+        ("d.near".to_string(), false),
+    ]);
+}
+
+#[test]
+fn test_refunds() {
+    // 5 holders of tickets win 7 prizes, b.near left the event and keeps its position without tickets
+    let export = load("refunds.json");
+    let verification = verify(&export).unwrap();
+
+    assert!(verification.is_valid());
+    assert!(verification.reserve_winners.is_empty());
+    assert_eq!(winners(&export), vec![
+        ("c.near".to_string(), false),
+        ("e.near".to_string(), false),
+        ("d.near".to_string(), false),
+        ("f.near".to_string(), false),
+        ("a.near".to_string(), false),
+        ("owner.near".to_string(), true),
+        ("owner.near".to_string(), true),
+    ]);
+}
+
+#[test]
+fn test_payout_mismatch() {
+    let mut export = load("reserve_winners.json");
+    export.payouts[1].account_id = "a.near".to_string();
+    let verification = verify(&export).unwrap();
+
+    assert!(!verification.is_valid());
+    assert_eq!(verification.mismatches.len(), 1);
+    assert_eq!(verification.mismatches[0].payout_index, 1);
+    assert_eq!(verification.mismatches[0].expected.as_deref(), Some("e.near"));
+}
+
+#[test]
+fn test_counter_mismatch() {
+    let mut export = load("reserve_winners.json");
+    export.event.draw.as_mut().unwrap().counter = 6;
+    let verification = verify(&export).unwrap();
+
+    assert!(verification.mismatches.is_empty());
+    assert!(!verification.counter_matches);
+    assert!(!verification.is_valid());
+}
+
+#[test]
+fn test_departed_participants_required() {
+    let mut export = load("reserve_winners.json");
+    export.participants.remove(1);
+
+    assert!(verify(&export).is_err());
+}